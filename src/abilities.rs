@@ -0,0 +1,472 @@
+use bevy::{input::common_conditions::input_just_pressed, prelude::*, utils::HashMap};
+use hexx::Hex;
+use serde::Deserialize;
+
+use crate::{
+    components::{
+        ActionPoints, BaseHex, BoardLoc, EffectiveStats, Faction, HexTile, Selected, Unit, UnitId,
+    },
+    constants::DOWNHILL_ATTACK_BONUS,
+    events::{CommandSubmitted, MouseClickedHex, MouseEnteredHex},
+    facing::AttackAngle,
+    game_command::GameCommand,
+    helpers::data::load_ron_dir,
+    line_of_sight::{line_of_sight, Sight, SightLines, SightRule},
+    replay::ReplayPlayback,
+    resources::{Elevation, HexMap, TerrainMap},
+    states::PlayerState,
+    status_effects::StatusEffect,
+    structures::Structures,
+    tiles::layers::{LayerAppExt, LayerAppearance},
+};
+
+type SelectedUnit = (With<Unit>, With<Selected>);
+type TargetingTile = Or<(With<AbilityTarget>, With<AbilityArea>)>;
+
+// Abilities are defined in `assets/abilities/*.ron`, and handed out to units by
+// name in their archetype.
+const ABILITIES_DIR: &str = "abilities";
+
+const ABILITY_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+// The hexes an ability hits, relative to the hex it's aimed at.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TargetShape {
+    Single,
+    // Every hex from the caster up to the target.
+    Line,
+    // Spreads out from the caster towards the target, this many hexes deep.
+    Cone(u32),
+    Ring(u32),
+    Radius(u32),
+    SelfOnly,
+}
+
+impl TargetShape {
+    pub fn area(&self, caster: Hex, target: Hex) -> Vec<Hex> {
+        match *self {
+            TargetShape::Single => vec![target],
+            TargetShape::Line => caster.line_to(target).skip(1).collect(),
+            TargetShape::Cone(length) => cone(caster, target, length),
+            TargetShape::Ring(radius) => target.ring(radius).collect(),
+            TargetShape::Radius(radius) => target.range(radius).collect(),
+            TargetShape::SelfOnly => vec![caster],
+        }
+    }
+}
+
+// Position of a hex on a grid of unit sized, evenly spaced hexes, so angles
+// between hexes aren't skewed by the tile art's proportions.
+pub fn unit_pos(hex: Hex) -> Vec2 {
+    Vec2::new(
+        1.5 * hex.x as f32,
+        3.0_f32.sqrt() * (hex.y as f32 + hex.x as f32 / 2.0),
+    )
+}
+
+fn cone(caster: Hex, target: Hex, length: u32) -> Vec<Hex> {
+    if caster == target {
+        return Vec::new();
+    }
+    let forward = (unit_pos(target) - unit_pos(caster)).normalize();
+    caster
+        .range(length)
+        .filter(|hex| *hex != caster)
+        .filter(|hex| {
+            let offset = (unit_pos(*hex) - unit_pos(caster)).normalize();
+            // A little over 30 degrees either side, so the edges of the cone
+            // include the hexes that sit exactly on the boundary.
+            forward.dot(offset) >= 0.85
+        })
+        .collect()
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum Affects {
+    Enemies,
+    Allies,
+    #[default]
+    All,
+}
+
+impl Affects {
+    pub fn includes(&self, caster: Faction, target: Faction) -> bool {
+        match self {
+            Affects::Enemies => caster != target,
+            Affects::Allies => caster == target,
+            Affects::All => true,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub enum AbilityEffect {
+    Damage(i32),
+    Heal(i32),
+    Status(StatusEffect),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AbilityDef {
+    pub name: String,
+    // Action points spent when the ability is used.
+    pub cost: u32,
+    // Turns before the ability can be used again.
+    pub cooldown: u32,
+    // How far from the caster the ability can be aimed.
+    pub range: u32,
+    pub shape: TargetShape,
+    #[serde(default)]
+    pub affects: Affects,
+    #[serde(default)]
+    pub line_of_sight: SightRule,
+    pub effects: Vec<AbilityEffect>,
+}
+
+impl AbilityDef {
+    // Ranged abilities reach further from structures that give vision.
+    pub fn valid_targets(
+        &self,
+        caster: Hex,
+        hex_map: &HexMap,
+        structures: &Structures,
+    ) -> Vec<Hex> {
+        if self.shape == TargetShape::SelfOnly {
+            return vec![caster];
+        }
+        let range = match self.range {
+            0 | 1 => self.range,
+            _ => self.range + structures.vision(caster),
+        };
+        caster
+            .range(range)
+            .filter(|hex| hex_map.0.contains(hex))
+            .collect()
+    }
+
+    // How well the caster sees `target`, as far as this ability cares.
+    pub fn sight_to(
+        &self,
+        caster: Hex,
+        target: Hex,
+        terrain: &TerrainMap,
+        elevation: &Elevation,
+        units: &[Hex],
+    ) -> Sight {
+        match self.line_of_sight {
+            SightRule::Terrain => line_of_sight(caster, target, terrain, elevation, &[]),
+            SightRule::TerrainAndUnits => line_of_sight(caster, target, terrain, elevation, units),
+            SightRule::Ignored => Sight::Clear,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct AbilityDefs(pub HashMap<String, AbilityDef>);
+
+impl AbilityDefs {
+    pub fn load() -> Self {
+        AbilityDefs(
+            load_ron_dir::<AbilityDef>(ABILITIES_DIR)
+                .into_iter()
+                .map(|ability| (ability.name.clone(), ability))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+pub struct Abilities(pub Vec<String>);
+
+// Turns left before each ability can be used again.
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+pub struct AbilityCooldowns(pub HashMap<String, u32>);
+
+impl AbilityCooldowns {
+    pub fn remaining(&self, ability: &str) -> u32 {
+        self.0.get(ability).copied().unwrap_or_default()
+    }
+
+    pub fn tick(&mut self) {
+        for remaining in self.0.values_mut() {
+            *remaining = remaining.saturating_sub(1);
+        }
+    }
+}
+
+pub fn can_use_ability(
+    ability: &AbilityDef,
+    stats: &EffectiveStats,
+    action_points: &ActionPoints,
+    cooldowns: &AbilityCooldowns,
+) -> bool {
+    stats.can_act
+        && action_points.current >= ability.cost
+        && cooldowns.remaining(&ability.name) == 0
+}
+
+// Damage dealt by an ability effect, attacking from `downhill` levels above the
+// target.
+pub fn ability_damage(
+    amount: i32,
+    attacker: &EffectiveStats,
+    target: &EffectiveStats,
+    downhill: u32,
+    angle: AttackAngle,
+    sight: Sight,
+) -> i32 {
+    (amount + attacker.attack - target.defense - sight.cover()
+        + downhill as i32 * DOWNHILL_ATTACK_BONUS
+        + angle.bonus())
+    .max(0)
+}
+
+pub struct AbilityTargeting {
+    pub caster: Entity,
+    pub ability: String,
+}
+
+// The ability the player is currently aiming, if any.
+#[derive(Resource, Default)]
+pub struct ActiveAbility(pub Option<AbilityTargeting>);
+
+// Tiles the active ability can be aimed at.
+#[derive(Component)]
+pub struct AbilityTarget;
+
+// Tiles the active ability would hit if aimed at the hovered hex.
+#[derive(Component)]
+pub struct AbilityArea;
+
+pub struct AbilitiesPlugin;
+
+impl Plugin for AbilitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AbilityDefs::load())
+            .init_resource::<ActiveAbility>()
+            .register_type::<Abilities>()
+            .register_type::<AbilityCooldowns>()
+            .register_layer::<AbilityTarget>(
+                "AbilityTarget",
+                1.5,
+                LayerAppearance::Color(Color::rgba(0.3, 0.5, 1.0, 0.45)),
+            )
+            .register_layer::<AbilityArea>(
+                "AbilityArea",
+                2.8,
+                LayerAppearance::Color(Color::rgba(1.0, 0.4, 0.1, 0.6)),
+            )
+            .add_systems(
+                Update,
+                start_targeting
+                    .run_if(in_state(PlayerState::UnitSelected))
+                    .run_if(not(resource_exists::<ReplayPlayback>())),
+            )
+            .add_systems(OnEnter(PlayerState::Targeting), add_ability_target_to_tiles)
+            .add_systems(
+                Update,
+                (
+                    preview_ability_area,
+                    confirm_ability_target,
+                    reselect_caster_on_right_click.run_if(input_just_pressed(MouseButton::Right)),
+                )
+                    .run_if(in_state(PlayerState::Targeting)),
+            )
+            .add_systems(OnExit(PlayerState::Targeting), clear_targeting);
+    }
+}
+
+fn start_targeting(
+    keyboard_input: Res<Input<KeyCode>>,
+    unit_q: Query<
+        (
+            Entity,
+            &Abilities,
+            &EffectiveStats,
+            &ActionPoints,
+            &AbilityCooldowns,
+        ),
+        SelectedUnit,
+    >,
+    ability_defs: Res<AbilityDefs>,
+    mut active_ability: ResMut<ActiveAbility>,
+    mut next_state: ResMut<NextState<PlayerState>>,
+) {
+    let Some(index) = ABILITY_KEYS
+        .iter()
+        .position(|key| keyboard_input.just_pressed(*key))
+    else {
+        return;
+    };
+    let Ok((caster, abilities, stats, action_points, cooldowns)) = unit_q.get_single() else {
+        return;
+    };
+    let Some(ability) = abilities
+        .0
+        .get(index)
+        .and_then(|name| ability_defs.0.get(name))
+    else {
+        return;
+    };
+    if !can_use_ability(ability, stats, action_points, cooldowns) {
+        return;
+    }
+    active_ability.0 = Some(AbilityTargeting {
+        caster,
+        ability: ability.name.clone(),
+    });
+    next_state.set(PlayerState::Targeting);
+}
+
+fn add_ability_target_to_tiles(
+    mut commands: Commands,
+    active_ability: Res<ActiveAbility>,
+    ability_defs: Res<AbilityDefs>,
+    caster_q: Query<&BoardLoc>,
+    unit_q: Query<&BoardLoc, With<Unit>>,
+    tile_q: Query<(Entity, &HexTile), With<BaseHex>>,
+    sight_lines: SightLines,
+) {
+    let Some(targeting) = &active_ability.0 else {
+        return;
+    };
+    let (Some(ability), Ok(caster_loc)) = (
+        ability_defs.0.get(&targeting.ability),
+        caster_q.get(targeting.caster),
+    ) else {
+        return;
+    };
+    let units: Vec<Hex> = unit_q.iter().map(|board_loc| board_loc.hex).collect();
+    let targets: Vec<Hex> = ability
+        .valid_targets(
+            caster_loc.hex,
+            &sight_lines.hex_map,
+            &sight_lines.structures,
+        )
+        .into_iter()
+        .filter(|target| {
+            ability.sight_to(
+                caster_loc.hex,
+                *target,
+                &sight_lines.terrain,
+                &sight_lines.elevation,
+                &units,
+            ) != Sight::Blocked
+        })
+        .collect();
+    for (entity, hex_tile) in tile_q.iter() {
+        if targets.contains(&hex_tile.0) {
+            commands.entity(entity).insert(AbilityTarget);
+        }
+    }
+}
+
+fn preview_ability_area(
+    mut commands: Commands,
+    mut ev_mouse_entered_hex: EventReader<MouseEnteredHex>,
+    active_ability: Res<ActiveAbility>,
+    ability_defs: Res<AbilityDefs>,
+    caster_q: Query<&BoardLoc>,
+    target_q: Query<&HexTile, With<AbilityTarget>>,
+    tile_q: Query<(Entity, &HexTile, Option<&AbilityArea>), With<BaseHex>>,
+) {
+    let Some(hovered) = ev_mouse_entered_hex.iter().last() else {
+        return;
+    };
+    let Some(targeting) = &active_ability.0 else {
+        return;
+    };
+    let (Some(ability), Ok(caster_loc)) = (
+        ability_defs.0.get(&targeting.ability),
+        caster_q.get(targeting.caster),
+    ) else {
+        return;
+    };
+    let area = if target_q.iter().any(|tile| tile.0 == hovered.0) {
+        ability.shape.area(caster_loc.hex, hovered.0)
+    } else {
+        Vec::new()
+    };
+    for (entity, hex_tile, in_area) in tile_q.iter() {
+        match (area.contains(&hex_tile.0), in_area.is_some()) {
+            (true, false) => {
+                commands.entity(entity).insert(AbilityArea);
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<AbilityArea>();
+            }
+            _ => (),
+        }
+    }
+}
+
+fn confirm_ability_target(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut ev_mouse_clicked_hex: EventReader<MouseClickedHex>,
+    active_ability: Res<ActiveAbility>,
+    target_q: Query<&HexTile, With<AbilityTarget>>,
+    caster_q: Query<&UnitId>,
+    mut ev_command_submitted: EventWriter<CommandSubmitted>,
+    mut next_state: ResMut<NextState<PlayerState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        next_state.set(PlayerState::Idle);
+        return;
+    }
+    let Some(clicked) = ev_mouse_clicked_hex.iter().last() else {
+        return;
+    };
+    // Clicking anywhere the ability can't reach cancels it.
+    if let Some(targeting) = &active_ability.0 {
+        if let Ok(unit_id) = caster_q.get(targeting.caster) {
+            if target_q.iter().any(|tile| tile.0 == clicked.0) {
+                ev_command_submitted.send(CommandSubmitted(GameCommand::UseAbility {
+                    unit: *unit_id,
+                    ability: targeting.ability.clone(),
+                    target: (clicked.0.x, clicked.0.y),
+                }));
+            }
+        }
+    }
+    next_state.set(PlayerState::Idle);
+}
+
+// Backs out of aiming, to the caster being selected.
+fn reselect_caster_on_right_click(
+    active_ability: Res<ActiveAbility>,
+    caster_q: Query<&UnitId>,
+    mut ev_command_submitted: EventWriter<CommandSubmitted>,
+) {
+    let Some(targeting) = &active_ability.0 else {
+        return;
+    };
+    if let Ok(unit_id) = caster_q.get(targeting.caster) {
+        ev_command_submitted.send(CommandSubmitted(GameCommand::SelectUnit(*unit_id)));
+    }
+}
+
+fn clear_targeting(
+    mut commands: Commands,
+    mut active_ability: ResMut<ActiveAbility>,
+    tile_q: Query<Entity, TargetingTile>,
+) {
+    active_ability.0 = None;
+    for entity in tile_q.iter() {
+        commands
+            .entity(entity)
+            .remove::<AbilityTarget>()
+            .remove::<AbilityArea>();
+    }
+}
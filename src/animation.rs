@@ -0,0 +1,208 @@
+use bevy::{prelude::*, utils::HashMap};
+use hexx::Direction;
+use serde::Deserialize;
+
+use crate::{
+    components::{BoardLoc, Moving},
+    events::{UnitArrived, UnitAttacked, UnitDied, UnitHurt},
+};
+
+#[derive(Deserialize, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AnimationKind {
+    #[default]
+    Idle,
+    Walk,
+    Attack,
+    Hurt,
+    Death,
+}
+
+// Mirrors `hexx::Direction` so clips can be given per-direction frames in data files.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClipDirection {
+    TopRight,
+    Top,
+    TopLeft,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl From<Direction> for ClipDirection {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::TopRight => ClipDirection::TopRight,
+            Direction::Top => ClipDirection::Top,
+            Direction::TopLeft => ClipDirection::TopLeft,
+            Direction::BottomLeft => ClipDirection::BottomLeft,
+            Direction::Bottom => ClipDirection::Bottom,
+            Direction::BottomRight => ClipDirection::BottomRight,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SpriteClip {
+    pub frames: Vec<usize>,
+    pub fps: f32,
+    pub looping: bool,
+    #[serde(default)]
+    pub directions: HashMap<ClipDirection, Vec<usize>>,
+}
+
+impl SpriteClip {
+    // Returns the frames to play when facing `direction`, and whether the sprite
+    // should be flipped. Directions without their own frames reuse the default
+    // frames, mirrored when facing left.
+    fn frames_for(&self, direction: Direction) -> (&[usize], Option<bool>) {
+        if let Some(frames) = self.directions.get(&ClipDirection::from(direction)) {
+            return (frames, Some(false));
+        }
+        let flip_x = match direction {
+            Direction::TopRight | Direction::BottomRight => Some(false),
+            Direction::TopLeft | Direction::BottomLeft => Some(true),
+            Direction::Top | Direction::Bottom => None,
+        };
+        (&self.frames, flip_x)
+    }
+}
+
+#[derive(Component, Deserialize, Clone, Debug)]
+#[serde(transparent)]
+pub struct SpriteClips(pub HashMap<AnimationKind, SpriteClip>);
+
+impl SpriteClips {
+    pub fn first_frame(&self) -> usize {
+        self.0
+            .get(&AnimationKind::Idle)
+            .and_then(|clip| clip.frames.first().copied())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+pub struct UnitAnimation {
+    pub kind: AnimationKind,
+    pub direction: Direction,
+    pub elapsed: f32,
+}
+
+impl UnitAnimation {
+    pub fn play(&mut self, kind: AnimationKind) {
+        // Dead units stay dead.
+        if self.kind == AnimationKind::Death {
+            return;
+        }
+        self.kind = kind;
+        self.elapsed = 0.0;
+    }
+}
+
+pub struct UnitAnimationPlugin;
+
+impl Plugin for UnitAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<UnitAnimation>().add_systems(
+            Update,
+            (
+                play_walk_animation,
+                play_idle_animation,
+                play_event_animations,
+                advance_animations,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn play_walk_animation(mut unit_q: Query<(&Moving, &mut UnitAnimation), Changed<Moving>>) {
+    for (moving, mut animation) in unit_q.iter_mut() {
+        animation.direction = moving.direction;
+        if animation.kind == AnimationKind::Idle {
+            animation.play(AnimationKind::Walk);
+        }
+    }
+}
+
+fn play_idle_animation(
+    mut ev_unit_arrived: EventReader<UnitArrived>,
+    mut unit_q: Query<&mut UnitAnimation>,
+) {
+    for ev in ev_unit_arrived.iter().filter(|ev| ev.end_of_path) {
+        if let Ok(mut animation) = unit_q.get_mut(ev.unit) {
+            if animation.kind == AnimationKind::Walk {
+                animation.play(AnimationKind::Idle);
+            }
+        }
+    }
+}
+
+fn play_event_animations(
+    mut ev_unit_attacked: EventReader<UnitAttacked>,
+    mut ev_unit_hurt: EventReader<UnitHurt>,
+    mut ev_unit_died: EventReader<UnitDied>,
+    board_loc_q: Query<&BoardLoc>,
+    mut unit_q: Query<&mut UnitAnimation>,
+) {
+    for ev in ev_unit_attacked.iter() {
+        if let Ok(mut animation) = unit_q.get_mut(ev.attacker) {
+            if let Ok([attacker, target]) = board_loc_q.get_many([ev.attacker, ev.target]) {
+                animation.direction = attacker.hex.main_direction_to(target.hex);
+            }
+            animation.play(AnimationKind::Attack);
+        }
+    }
+    for ev in ev_unit_hurt.iter() {
+        if let Ok(mut animation) = unit_q.get_mut(ev.0) {
+            animation.play(AnimationKind::Hurt);
+        }
+    }
+    for ev in ev_unit_died.iter() {
+        if let Ok(mut animation) = unit_q.get_mut(ev.0) {
+            animation.play(AnimationKind::Death);
+        }
+    }
+}
+
+fn advance_animations(
+    time: Res<Time>,
+    mut unit_q: Query<(
+        &SpriteClips,
+        &mut UnitAnimation,
+        &mut TextureAtlasSprite,
+        Option<&Moving>,
+    )>,
+) {
+    for (clips, mut animation, mut sprite, moving) in unit_q.iter_mut() {
+        let Some(clip) = clips.0.get(&animation.kind) else {
+            continue;
+        };
+        let (frames, flip_x) = clip.frames_for(animation.direction);
+        if frames.is_empty() {
+            continue;
+        }
+        if let Some(flip_x) = flip_x {
+            sprite.flip_x = flip_x;
+        }
+
+        animation.elapsed += time.delta_seconds();
+        let mut frame = (animation.elapsed * clip.fps) as usize;
+        if frame >= frames.len() {
+            if clip.looping {
+                frame %= frames.len();
+            } else if animation.kind == AnimationKind::Death {
+                frame = frames.len() - 1;
+            } else {
+                // One-shot clips hand back to whatever the unit is doing.
+                let next = match moving {
+                    Some(_) => AnimationKind::Walk,
+                    None => AnimationKind::Idle,
+                };
+                animation.play(next);
+                continue;
+            }
+        }
+        sprite.index = frames[frame];
+    }
+}
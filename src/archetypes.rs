@@ -0,0 +1,106 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use crate::{animation::SpriteClips, helpers::data::load_ron_dir};
+
+// Unit archetypes are defined in `assets/units/*.ron`, one file per archetype.
+const ARCHETYPES_DIR: &str = "units";
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SpriteSheet {
+    pub path: String,
+    pub frame_size: (f32, f32),
+    pub columns: usize,
+    pub rows: usize,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnitTrait {
+    // Moves through enemy zones of control without stopping.
+    Skirmisher,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct UnitArchetype {
+    pub name: String,
+    pub health: i32,
+    pub move_range: u32,
+    #[serde(default = "default_attack_range")]
+    pub attack_range: u32,
+    #[serde(default)]
+    pub attack: i32,
+    #[serde(default)]
+    pub defense: i32,
+    #[serde(default = "default_action_points")]
+    pub action_points: u32,
+    // Names of the abilities in `assets/abilities`, bound to keys 1-9 in order.
+    #[serde(default)]
+    pub abilities: Vec<String>,
+    #[serde(default)]
+    pub traits: Vec<UnitTrait>,
+    // Gold it takes to recruit one.
+    #[serde(default)]
+    pub cost: u32,
+    pub sprite_sheet: SpriteSheet,
+    pub clips: SpriteClips,
+}
+
+fn default_attack_range() -> u32 {
+    1
+}
+
+fn default_action_points() -> u32 {
+    2
+}
+
+impl UnitArchetype {
+    pub fn sprite_sheet_bundle(
+        &self,
+        asset_server: &AssetServer,
+        texture_atlases: &mut Assets<TextureAtlas>,
+        transform: Transform,
+    ) -> SpriteSheetBundle {
+        let (width, height) = self.sprite_sheet.frame_size;
+        let texture_atlas = TextureAtlas::from_grid(
+            asset_server.load(self.sprite_sheet.path.as_str()),
+            Vec2::new(width, height),
+            self.sprite_sheet.columns,
+            self.sprite_sheet.rows,
+            None,
+            None,
+        );
+        SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(texture_atlas),
+            sprite: TextureAtlasSprite::new(self.clips.first_frame()),
+            transform,
+            ..default()
+        }
+    }
+}
+
+#[derive(Resource, Default, Clone)]
+pub struct UnitArchetypes(pub HashMap<String, UnitArchetype>);
+
+impl UnitArchetypes {
+    pub fn load() -> Self {
+        UnitArchetypes(
+            load_ron_dir::<UnitArchetype>(ARCHETYPES_DIR)
+                .into_iter()
+                .map(|archetype| (archetype.name.clone(), archetype))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Component, Reflect, Default, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Archetype(pub String);
+
+pub struct ArchetypesPlugin;
+
+impl Plugin for ArchetypesPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Archetype>()
+            .insert_resource(UnitArchetypes::load());
+    }
+}
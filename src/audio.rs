@@ -0,0 +1,189 @@
+use bevy::{
+    audio::{AudioSinkPlayback, Volume},
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::HashMap,
+};
+use serde::Deserialize;
+
+use crate::{
+    events::{
+        MouseEnteredHex, MoveTargetConfirmed, TurnPassed, UnitArrived, UnitAttacked, UnitSelected,
+    },
+    helpers::data::load_ron,
+    settings::Settings,
+    states::AppState,
+};
+
+// Maps gameplay sounds and music tracks to audio files, so they can be swapped
+// without touching code.
+const SOUNDS_PATH: &str = "audio/sounds.ron";
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    Hover,
+    Select,
+    Confirm,
+    Step,
+    Attack,
+    TurnChange,
+}
+
+#[derive(Component, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MusicTrack {
+    Menu,
+    Battle,
+}
+
+impl MusicTrack {
+    fn for_state(state: &AppState) -> MusicTrack {
+        match state {
+            AppState::LoadingMap | AppState::GameOver | AppState::Editor | AppState::Lobby => {
+                MusicTrack::Menu
+            }
+            AppState::InGame => MusicTrack::Battle,
+        }
+    }
+}
+
+#[derive(Resource, Deserialize, Default, Clone, Debug)]
+pub struct SoundMappings {
+    pub effects: HashMap<SoundEffect, String>,
+    pub music: HashMap<MusicTrack, String>,
+    pub crossfade_seconds: f32,
+}
+
+// How loud a music track currently is relative to the music volume. Tracks fade
+// towards `target`, and are despawned once they've faded out completely.
+#[derive(Component)]
+struct MusicFade {
+    level: f32,
+    target: f32,
+}
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_ron::<SoundMappings>(SOUNDS_PATH).unwrap_or_default())
+            .add_systems(Update, (play_event_sounds, change_music, fade_music));
+    }
+}
+
+// Everything needed to play a sound effect at the current volume.
+#[derive(SystemParam)]
+struct SoundPlayer<'w, 's> {
+    commands: Commands<'w, 's>,
+    asset_server: Res<'w, AssetServer>,
+    sounds: Res<'w, SoundMappings>,
+    settings: Res<'w, Settings>,
+}
+
+impl SoundPlayer<'_, '_> {
+    fn play(&mut self, effect: SoundEffect) {
+        if let Some(path) = self.sounds.effects.get(&effect) {
+            self.commands.spawn(AudioBundle {
+                source: self.asset_server.load(path.as_str()),
+                settings: PlaybackSettings::DESPAWN
+                    .with_volume(Volume::new_relative(self.settings.volume.sfx_volume())),
+            });
+        }
+    }
+}
+
+fn play_event_sounds(
+    mut sound_player: SoundPlayer,
+    mut ev_mouse_entered_hex: EventReader<MouseEnteredHex>,
+    mut ev_unit_selected: EventReader<UnitSelected>,
+    mut ev_move_target_confirmed: EventReader<MoveTargetConfirmed>,
+    mut ev_unit_arrived: EventReader<UnitArrived>,
+    mut ev_unit_attacked: EventReader<UnitAttacked>,
+    mut ev_turn_passed: EventReader<TurnPassed>,
+) {
+    let mut effects = Vec::new();
+    effects.extend(ev_mouse_entered_hex.iter().map(|_| SoundEffect::Hover));
+    effects.extend(ev_unit_selected.iter().map(|_| SoundEffect::Select));
+    effects.extend(
+        ev_move_target_confirmed
+            .iter()
+            .map(|_| SoundEffect::Confirm),
+    );
+    effects.extend(ev_unit_arrived.iter().map(|_| SoundEffect::Step));
+    effects.extend(ev_unit_attacked.iter().map(|_| SoundEffect::Attack));
+    effects.extend(ev_turn_passed.iter().map(|_| SoundEffect::TurnChange));
+
+    // Several hexes can be stepped on in a single frame at fast animation speeds,
+    // there's no point in playing the same sound on top of itself.
+    effects.dedup();
+    for effect in effects {
+        sound_player.play(effect);
+    }
+}
+
+fn change_music(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    sounds: Res<SoundMappings>,
+    app_state: Res<State<AppState>>,
+    mut music_q: Query<(&MusicTrack, &mut MusicFade)>,
+) {
+    if !app_state.is_changed() {
+        return;
+    }
+    let track = MusicTrack::for_state(app_state.get());
+    let mut already_playing = false;
+    for (playing, mut fade) in music_q.iter_mut() {
+        if *playing == track {
+            fade.target = 1.0;
+            already_playing = true;
+        } else {
+            fade.target = 0.0;
+        }
+    }
+    if already_playing {
+        return;
+    }
+    if let Some(path) = sounds.music.get(&track) {
+        commands.spawn((
+            AudioBundle {
+                source: asset_server.load(path.as_str()),
+                settings: PlaybackSettings::LOOP.with_volume(Volume::new_relative(0.0)),
+            },
+            track,
+            MusicFade {
+                level: 0.0,
+                target: 1.0,
+            },
+            Name::new(format!("Music {:?}", track)),
+        ));
+    }
+}
+
+fn fade_music(
+    mut commands: Commands,
+    time: Res<Time>,
+    sounds: Res<SoundMappings>,
+    settings: Res<Settings>,
+    mut music_q: Query<(Entity, &mut MusicFade, Option<&AudioSink>)>,
+) {
+    let step = if sounds.crossfade_seconds > 0.0 {
+        time.delta_seconds() / sounds.crossfade_seconds
+    } else {
+        1.0
+    };
+    for (entity, mut fade, sink) in music_q.iter_mut() {
+        if fade.level < fade.target {
+            fade.level = (fade.level + step).min(fade.target);
+        } else if fade.level > fade.target {
+            fade.level = (fade.level - step).max(fade.target);
+        }
+        if fade.level <= 0.0 && fade.target <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // The sink only shows up once the track has loaded and started playing.
+        if let Some(sink) = sink {
+            sink.set_volume(fade.level * settings.volume.music_volume());
+        }
+    }
+}
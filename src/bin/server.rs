@@ -0,0 +1,33 @@
+// Runs matches with nothing drawn, for players (or bots) to join over the
+// network. Run it from the project folder, so it finds the assets:
+//
+//     cargo run --bin server -- [address] [map] [players]
+use std::env;
+
+use bevy::{app::ScheduleRunnerPlugin, log::LogPlugin, prelude::*, utils::Duration};
+use bevy_toy_project::{
+    map::DEFAULT_MAP,
+    network::DEFAULT_ADDRESS,
+    server::{ServerConfig, ServerPlugin},
+};
+
+const TICKS_PER_SECOND: f64 = 30.0;
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let config = ServerConfig {
+        address: args.next().unwrap_or_else(|| String::from(DEFAULT_ADDRESS)),
+        map: args.next().unwrap_or_else(|| String::from(DEFAULT_MAP)),
+        players: args.next().and_then(|x| x.parse().ok()).unwrap_or(2),
+    };
+    App::new()
+        .add_plugins(
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+                1.0 / TICKS_PER_SECOND,
+            ))),
+        )
+        .add_plugins(LogPlugin::default())
+        .insert_resource(config)
+        .add_plugins(ServerPlugin)
+        .run();
+}
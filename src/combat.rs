@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{BoardLoc, Selectable, Selected, Unit},
+    events::{DamageDealt, UnitDied, UnitHealed, UnitHurt},
+};
+
+// How long a dead unit stays on the board, so its death animation can play.
+const DEATH_DURATION: f32 = 1.0;
+
+#[derive(Component)]
+pub struct Dying(pub Timer);
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (apply_damage, apply_healing, despawn_dead_units).chain(),
+        );
+    }
+}
+
+fn apply_damage(
+    mut commands: Commands,
+    mut ev_damage_dealt: EventReader<DamageDealt>,
+    mut unit_q: Query<&mut Unit>,
+    mut ev_unit_hurt: EventWriter<UnitHurt>,
+    mut ev_unit_died: EventWriter<UnitDied>,
+) {
+    for ev in ev_damage_dealt.iter() {
+        let Ok(mut unit) = unit_q.get_mut(ev.target) else {
+            continue;
+        };
+        // Already dead from an earlier hit this frame.
+        if unit.health <= 0 {
+            continue;
+        }
+        unit.health -= ev.amount;
+        if unit.health > 0 {
+            ev_unit_hurt.send(UnitHurt(ev.target));
+            continue;
+        }
+        ev_unit_died.send(UnitDied(ev.target));
+        // Dead units leave the board straight away, and are despawned once their
+        // death animation has played.
+        commands
+            .entity(ev.target)
+            .remove::<(Unit, BoardLoc, Selectable, Selected)>()
+            .insert(Dying(Timer::from_seconds(DEATH_DURATION, TimerMode::Once)));
+    }
+}
+
+fn apply_healing(mut ev_unit_healed: EventReader<UnitHealed>, mut unit_q: Query<&mut Unit>) {
+    for ev in ev_unit_healed.iter() {
+        if let Ok(mut unit) = unit_q.get_mut(ev.target) {
+            if unit.health > 0 {
+                unit.health = (unit.health + ev.amount).min(unit.max_health);
+            }
+        }
+    }
+}
+
+fn despawn_dead_units(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut dying_q: Query<(Entity, &mut Dying)>,
+) {
+    for (entity, mut dying) in dying_q.iter_mut() {
+        if dying.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
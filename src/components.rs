@@ -1,58 +1,195 @@
-use bevy::prelude::*;
-use hexx::{Direction, Hex};
-
-use crate::enums::MapLayer;
-
-#[derive(Component, Copy, Clone)]
-pub struct Hovered;
-
-#[derive(Component, Reflect)]
-pub struct Unit {
-    pub health: i32,
-}
-
-#[derive(Component, Reflect)]
-pub struct MoveRange(pub u32);
-
-#[derive(Component, Reflect, Default, Clone, Copy, Debug, PartialEq)]
-#[reflect(Component)]
-pub struct BoardLoc {
-    pub hex: Hex,
-}
-
-#[derive(Component)]
-pub struct Selected;
-
-#[derive(Component)]
-pub struct Selectable;
-
-#[derive(Component)]
-pub struct Activated;
-
-#[derive(Component)]
-pub struct BaseHex;
-
-#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
-#[reflect(Component)]
-pub struct HexTile(pub Hex);
-
-#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
-#[reflect(Component)]
-pub struct Layer {
-    pub layer_type: MapLayer,
-}
-
-#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
-#[reflect(Component)]
-pub struct MoveTarget(pub Hex);
-
-#[derive(Component, PartialEq, Reflect, Default, Clone, Copy, Debug)]
-#[reflect(Component)]
-pub struct Moving {
-    pub towards: Hex,
-    pub direction: Direction,
-}
-
-#[derive(Component, Reflect, Default, Clone, Debug, PartialEq)]
-#[reflect(Component)]
-pub struct Path(pub Vec<Hex>);
+use bevy::prelude::*;
+use hexx::{Direction, Hex};
+use serde::{Deserialize, Serialize};
+
+use crate::{helpers::tween::Easing, resources::AnimationSpeed};
+
+#[derive(Component, Copy, Clone)]
+pub struct Hovered;
+
+#[derive(Component, Reflect)]
+pub struct Unit {
+    pub health: i32,
+    pub max_health: i32,
+}
+
+#[derive(Component, Reflect)]
+pub struct MoveRange(pub u32);
+
+#[derive(Component, Reflect)]
+pub struct AttackRange(pub u32);
+
+// Added to the damage a unit deals.
+#[derive(Component, Reflect)]
+pub struct Attack(pub i32);
+
+// Taken off the damage a unit receives.
+#[derive(Component, Reflect)]
+pub struct Defense(pub i32);
+
+// A unit's stats once its status effects are applied. Anything that moves or
+// fights should read these rather than the base stats.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct EffectiveStats {
+    pub move_range: u32,
+    pub attack: i32,
+    pub defense: i32,
+    pub can_act: bool,
+}
+
+// Spent on abilities, and refilled at the start of each turn.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct ActionPoints {
+    pub current: u32,
+    pub max: u32,
+}
+
+// Movement used up this turn, out of the unit's effective move range.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct MovementSpent(pub u32);
+
+#[derive(Component, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[reflect(Component)]
+pub struct Faction(pub u32);
+
+impl Faction {
+    pub fn color(&self) -> Color {
+        match self.0 {
+            0 => Color::WHITE,
+            1 => Color::rgb(1.0, 0.6, 0.6),
+            2 => Color::rgb(0.6, 0.7, 1.0),
+            _ => Color::rgb(0.8, 1.0, 0.6),
+        }
+    }
+}
+
+#[derive(Component, Reflect, Default, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct BoardLoc {
+    pub hex: Hex,
+}
+
+#[derive(Component)]
+pub struct Selected;
+
+#[derive(Component)]
+pub struct Selectable;
+
+// Recruited on this turn number, and can't be given orders until the next.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Recruited(pub i32);
+
+#[derive(Component)]
+pub struct Activated;
+
+#[derive(Component)]
+pub struct BaseHex;
+
+// Parent of every `BaseHex` tile.
+#[derive(Component)]
+pub struct BaseLayer;
+
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct HexTile(pub Hex);
+
+// Names a unit in commands and replays. Ids are handed out in spawn order, so a
+// match rebuilt from the same map gives every unit the same id.
+#[derive(
+    Component, Reflect, Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq, Hash,
+)]
+#[reflect(Component)]
+pub struct UnitId(pub u32);
+
+// Index of a unit in the map file it was spawned from.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct MapUnit(pub usize);
+
+// Index of a layer in the `LayerRegistry`.
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LayerId(pub usize);
+
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Layer {
+    pub id: LayerId,
+}
+
+// The hex the selected unit would move to, or attack, if the order is confirmed.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct MoveTarget(pub Hex);
+
+// Tiles the selected unit would pass through on its way to the `MoveTarget`.
+#[derive(Component)]
+pub struct PathPreview;
+
+// Marks a previewed `MoveTarget` in an enemy's zone of control, where the unit
+// will be stopped.
+#[derive(Component)]
+pub struct ZoneOfControlStop;
+
+// Which way a unit looks. Attacks from its flanks or from behind hit harder.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Facing(pub Direction);
+
+// Isn't stopped by enemy zones of control.
+#[derive(Component)]
+pub struct Skirmisher;
+
+#[derive(Component, PartialEq, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Moving {
+    pub towards: Hex,
+    pub direction: Direction,
+}
+
+#[derive(Component, Reflect, Default, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Path(pub Vec<Hex>);
+
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct MoveTween {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub elapsed: f32,
+    pub duration: f32,
+    pub easing: Easing,
+}
+
+impl MoveTween {
+    pub fn new(start: Vec2, end: Vec2, easing: Easing, speed: AnimationSpeed) -> Self {
+        let duration = match speed.units_per_second() {
+            Some(units_per_second) => {
+                start.distance(end) / units_per_second * easing.duration_scale()
+            }
+            None => 0.0,
+        };
+        MoveTween {
+            start,
+            end,
+            elapsed: 0.0,
+            duration,
+            easing,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    pub fn position(&self) -> Vec2 {
+        if self.is_finished() {
+            return self.end;
+        }
+        let t = self.easing.apply(self.elapsed / self.duration);
+        self.start.lerp(self.end, t)
+    }
+}
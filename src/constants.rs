@@ -1,14 +1,33 @@
-use hexx::{Hex, HexLayout, Vec2};
+use hexx::Hex;
 
-pub const HEX_SIZE: Vec2 = Vec2::new(32.0, 18.0);
+use crate::components::Faction;
 
-pub const ORIGIN: Vec2 = Vec2::ZERO;
+// How far up a hex is drawn for each level of elevation.
+pub const ELEVATION_STEP: f32 = 8.0;
+
+pub const CLIFF_TEXTURE: &str = "cliff-side.png";
+
+// Extra damage dealt per level the attacker stands above its target.
+pub const DOWNHILL_ATTACK_BONUS: i32 = 1;
+
+// Extra damage dealt hitting a unit from the side, or from behind.
+pub const FLANK_ATTACK_BONUS: i32 = 1;
+pub const REAR_ATTACK_BONUS: i32 = 2;
+
+// Defense gained by a unit that is only partly in sight of its attacker.
+pub const COVER_DEFENSE_BONUS: i32 = 1;
+
+// Draw layers for the board's tiles, and the structures and units standing on
+// it. Overlay layers sit in between.
+pub const TILE_Z: f32 = 0.0;
+pub const STRUCTURE_Z: f32 = 4.0;
+pub const UNIT_Z: f32 = 10.0;
+
+pub const BASE_TILE_TEXTURE: &str = "grass-tile.png";
+
+// Plain white tile that colored layers tint.
+pub const OVERLAY_TILE_TEXTURE: &str = "overlay-tile.png";
+
+pub const PLAYER_FACTION: Faction = Faction(0);
 
 pub const CENTER_HEX: Hex = Hex { x: 0, y: 0 };
-pub const LAYOUT: HexLayout = HexLayout {
-    hex_size: HEX_SIZE,
-    orientation: hexx::HexOrientation::Flat,
-    origin: ORIGIN,
-    invert_y: false,
-    invert_x: false,
-};
@@ -0,0 +1,407 @@
+use bevy::{
+    ecs::system::SystemParam, input::common_conditions::input_just_pressed, prelude::*,
+    utils::HashMap, window::PrimaryWindow,
+};
+use hexx::Hex;
+
+use crate::{
+    abilities::{AbilityDefs, AbilityTargeting, ActiveAbility},
+    components::{ActionPoints, EffectiveStats, Faction, Selectable, Unit, UnitId},
+    events::CommandSubmitted,
+    game_command::{BoardState, GameCommand},
+    helpers::board::Board,
+    hot_seat::Players,
+    replay::ReplayPlayback,
+    resources::CursorPos,
+    rules::MatchState,
+    states::{AppState, PlayerState},
+    status_effects::StatusEffects,
+};
+
+const MENU_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_MENU_BUTTON: Color = Color::rgb(0.3, 0.3, 0.3);
+
+type UnitDetails = (
+    &'static Name,
+    &'static Unit,
+    &'static Faction,
+    &'static EffectiveStats,
+    &'static ActionPoints,
+    &'static StatusEffects,
+);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MenuAction {
+    // Selects the unit, to pick where it goes.
+    Move(UnitId),
+    Attack { unit: UnitId, target: UnitId },
+    // Starts aiming the ability.
+    Ability { caster: Entity, ability: String },
+    Wait(UnitId),
+    Inspect(Entity),
+}
+
+#[derive(Component)]
+pub struct ContextMenu;
+
+#[derive(Component)]
+pub struct ContextMenuButton(pub MenuAction);
+
+// The unit whose details are shown, if any.
+#[derive(Resource, Default)]
+pub struct Inspected(pub Option<Entity>);
+
+#[derive(Component)]
+pub struct InspectPanel;
+
+pub struct ContextMenuPlugin;
+
+impl Plugin for ContextMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inspected>()
+            .add_systems(Startup, spawn_inspect_panel)
+            .add_systems(
+                Update,
+                open_context_menu
+                    .run_if(input_just_pressed(MouseButton::Right))
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(in_state(PlayerState::Idle))
+                    .run_if(not(resource_exists::<ReplayPlayback>())),
+            )
+            .add_systems(
+                Update,
+                (press_menu_button, close_context_menu)
+                    .chain()
+                    .run_if(in_state(PlayerState::ContextMenu)),
+            )
+            .add_systems(OnExit(PlayerState::ContextMenu), despawn_context_menu)
+            .add_systems(Update, update_inspect_panel);
+    }
+}
+
+// Everything `unit` could be told to do right now, as far as the rules allow.
+fn unit_actions(
+    state: &MatchState,
+    unit: UnitId,
+    entity: Entity,
+    names: &HashMap<UnitId, String>,
+    ability_defs: &AbilityDefs,
+) -> Vec<(MenuAction, String)> {
+    let Some(unit_state) = state.unit(unit) else {
+        return Vec::new();
+    };
+    let faction = state.turn_queue.active_faction();
+    let allowed = |command: GameCommand| state.validate(faction, &command, ability_defs).is_ok();
+    let mut actions = Vec::new();
+
+    let can_move = state
+        .movement_range(unit_state)
+        .into_iter()
+        .filter(|hex| *hex != unit_state.hex)
+        .any(|hex| {
+            allowed(GameCommand::Move {
+                unit,
+                to: (hex.x, hex.y),
+            })
+        });
+    if can_move {
+        actions.push((MenuAction::Move(unit), String::from("Move")));
+    }
+    for target in state.units.iter() {
+        if allowed(GameCommand::Attack {
+            unit,
+            target: target.id,
+        }) {
+            actions.push((
+                MenuAction::Attack {
+                    unit,
+                    target: target.id,
+                },
+                format!(
+                    "Attack {}",
+                    names.get(&target.id).cloned().unwrap_or_default()
+                ),
+            ));
+        }
+    }
+    for ability in unit_state
+        .abilities
+        .iter()
+        .filter_map(|name| ability_defs.0.get(name))
+    {
+        let usable = ability
+            .valid_targets(unit_state.hex, &state.hex_map, &state.structures)
+            .into_iter()
+            .any(|target| {
+                allowed(GameCommand::UseAbility {
+                    unit,
+                    ability: ability.name.clone(),
+                    target: (target.x, target.y),
+                })
+            });
+        if usable {
+            actions.push((
+                MenuAction::Ability {
+                    caster: entity,
+                    ability: ability.name.clone(),
+                },
+                format!("{} - {} AP", ability.name, ability.cost),
+            ));
+        }
+    }
+    if allowed(GameCommand::Wait(unit)) {
+        actions.push((MenuAction::Wait(unit), String::from("Wait")));
+    }
+    actions
+}
+
+// Where the menu was asked for, on the board and on the screen.
+#[derive(SystemParam)]
+struct MenuCursor<'w, 's> {
+    cursor_pos: Res<'w, CursorPos>,
+    window_q: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    board: Board<'w>,
+}
+
+impl<'w, 's> MenuCursor<'w, 's> {
+    fn hex(&self) -> Hex {
+        self.board.world_pos_to_hex(self.cursor_pos.0)
+    }
+
+    fn screen_pos(&self) -> Vec2 {
+        self.window_q
+            .get_single()
+            .ok()
+            .and_then(|window| window.cursor_position())
+            .unwrap_or_default()
+    }
+}
+
+// Right-clicking closes the unit details if they're open, and otherwise offers
+// what can be done on the hex.
+fn open_context_menu(
+    mut commands: Commands,
+    cursor: MenuCursor,
+    unit_q: Query<(Entity, &UnitId, &Name, Option<&Selectable>), With<Unit>>,
+    board_state: BoardState,
+    ability_defs: Res<AbilityDefs>,
+    mut inspected: ResMut<Inspected>,
+    mut next_state: ResMut<NextState<PlayerState>>,
+) {
+    if inspected.0.take().is_some() {
+        return;
+    }
+    let hex = cursor.hex();
+    let state = board_state.snapshot();
+    let Some(occupant) = state.occupant(hex) else {
+        return;
+    };
+    let Some((entity, _, _, selectable)) = unit_q.iter().find(|(_, id, _, _)| **id == occupant.id)
+    else {
+        return;
+    };
+    let names: HashMap<UnitId, String> = unit_q
+        .iter()
+        .map(|(_, id, name, _)| (*id, name.to_string()))
+        .collect();
+    let mut actions = match selectable {
+        Some(_) => unit_actions(&state, occupant.id, entity, &names, &ability_defs),
+        None => Vec::new(),
+    };
+    actions.push((MenuAction::Inspect(entity), String::from("Inspect")));
+    spawn_context_menu(&mut commands, cursor.screen_pos(), hex, actions);
+    next_state.set(PlayerState::ContextMenu);
+}
+
+fn spawn_context_menu(
+    commands: &mut Commands,
+    position: Vec2,
+    hex: Hex,
+    actions: Vec<(MenuAction, String)>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(position.x),
+                    top: Val::Px(position.y),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    row_gap: Val::Px(2.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                z_index: ZIndex::Global(50),
+                ..default()
+            },
+            ContextMenu,
+            Name::new(format!("Context Menu {},{}", hex.x, hex.y)),
+        ))
+        .with_children(|parent| {
+            for (action, label) in actions {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                min_width: Val::Px(140.0),
+                                padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                                ..default()
+                            },
+                            background_color: MENU_BUTTON.into(),
+                            ..default()
+                        },
+                        ContextMenuButton(action),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font_size: 18.0,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn press_menu_button(
+    mut button_q: Query<
+        (&Interaction, &ContextMenuButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut active_ability: ResMut<ActiveAbility>,
+    mut inspected: ResMut<Inspected>,
+    mut ev_command_submitted: EventWriter<CommandSubmitted>,
+    mut next_state: ResMut<NextState<PlayerState>>,
+) {
+    for (interaction, button, mut color) in button_q.iter_mut() {
+        match interaction {
+            Interaction::Pressed => (),
+            Interaction::Hovered => {
+                *color = HOVERED_MENU_BUTTON.into();
+                continue;
+            }
+            Interaction::None => {
+                *color = MENU_BUTTON.into();
+                continue;
+            }
+        }
+        // Selecting a unit moves on to `UnitSelected` by itself.
+        match &button.0 {
+            MenuAction::Move(unit) => {
+                ev_command_submitted.send(CommandSubmitted(GameCommand::SelectUnit(*unit)));
+            }
+            MenuAction::Attack { unit, target } => {
+                ev_command_submitted.send(CommandSubmitted(GameCommand::Attack {
+                    unit: *unit,
+                    target: *target,
+                }));
+                next_state.set(PlayerState::Idle);
+            }
+            MenuAction::Ability { caster, ability } => {
+                active_ability.0 = Some(AbilityTargeting {
+                    caster: *caster,
+                    ability: ability.clone(),
+                });
+                next_state.set(PlayerState::Targeting);
+            }
+            MenuAction::Wait(unit) => {
+                ev_command_submitted.send(CommandSubmitted(GameCommand::Wait(*unit)));
+                next_state.set(PlayerState::Idle);
+            }
+            MenuAction::Inspect(unit) => {
+                inspected.0 = Some(*unit);
+                next_state.set(PlayerState::Idle);
+            }
+        }
+    }
+}
+
+// Right-clicking, or clicking anywhere off the menu, backs out of it.
+fn close_context_menu(
+    mouse_input: Res<Input<MouseButton>>,
+    button_q: Query<&Interaction, With<ContextMenuButton>>,
+    mut next_state: ResMut<NextState<PlayerState>>,
+) {
+    let on_menu = button_q
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if mouse_input.just_pressed(MouseButton::Right)
+        || (mouse_input.just_pressed(MouseButton::Left) && !on_menu)
+    {
+        next_state.set(PlayerState::Idle);
+    }
+}
+
+fn despawn_context_menu(mut commands: Commands, menu_q: Query<Entity, With<ContextMenu>>) {
+    for entity in menu_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_inspect_panel(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 18.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(12.0),
+            left: Val::Px(10.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        })
+        .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.5)),
+        InspectPanel,
+        Name::new("Inspect Panel"),
+    ));
+}
+
+fn update_inspect_panel(
+    mut inspected: ResMut<Inspected>,
+    players: Res<Players>,
+    unit_q: Query<UnitDetails>,
+    mut panel_q: Query<(&mut Text, &mut Visibility), With<InspectPanel>>,
+) {
+    let details = inspected.0.and_then(|entity| unit_q.get(entity).ok());
+    // The unit may have died since.
+    if inspected.0.is_some() && details.is_none() {
+        inspected.0 = None;
+    }
+    let value = match details {
+        Some((name, unit, faction, stats, action_points, status_effects)) => {
+            let mut lines = vec![
+                format!("{} ({})", name, players.name(*faction)),
+                format!("Health {}/{}", unit.health, unit.max_health),
+                format!(
+                    "Attack {}, defense {}, move {}",
+                    stats.attack, stats.defense, stats.move_range
+                ),
+                format!("AP {}/{}", action_points.current, action_points.max),
+            ];
+            for effect in status_effects.0.iter() {
+                lines.push(format!("{:?} for {} turns", effect.kind, effect.turns));
+            }
+            lines.push(String::from("Right-click to close"));
+            lines.join("\n")
+        }
+        None => String::new(),
+    };
+    for (mut text, mut visibility) in panel_q.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+        let shown = if value.is_empty() {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+        visibility.set_if_neq(shown);
+    }
+}
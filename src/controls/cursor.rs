@@ -1,6 +1,11 @@
 use bevy::prelude::*;
 
-use crate::{events::MouseClicked, resources::CursorPos, states::AppState};
+use crate::{
+    events::MouseClicked,
+    replay::ReplayPlayback,
+    resources::CursorPos,
+    states::{AppState, PlayerState},
+};
 
 pub struct CursorPlugin;
 
@@ -8,7 +13,16 @@ impl Plugin for CursorPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            (update_cursor_pos, cursor_clicked).run_if(in_state(AppState::InGame)),
+            update_cursor_pos
+                .run_if(in_state(AppState::InGame).or_else(in_state(AppState::Editor))),
+        )
+        .add_systems(
+            Update,
+            cursor_clicked
+                .run_if(in_state(AppState::InGame))
+                // Replays play themselves.
+                .run_if(not(resource_exists::<ReplayPlayback>()))
+                .run_if(not(in_state(PlayerState::PassingDevice))),
         );
     }
 }
@@ -39,11 +53,19 @@ pub fn update_cursor_pos(
 pub fn cursor_clicked(
     btn: Res<Input<MouseButton>>,
     cursor_pos: Res<CursorPos>,
+    interaction_q: Query<&Interaction>,
     mut ev_mouse_clicked: EventWriter<MouseClicked>,
 ) {
     if !btn.just_pressed(MouseButton::Left) {
         return;
     }
+    // Clicks on buttons and menus don't reach the board underneath.
+    if interaction_q
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
 
     ev_mouse_clicked.send(MouseClicked(cursor_pos.0));
 }
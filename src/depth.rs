@@ -0,0 +1,44 @@
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::helpers::board::Board;
+
+// How much nearer a sprite is drawn per world unit it sits lower on screen. Small
+// enough that a whole map fits between two layers.
+const DEPTH_PER_UNIT: f32 = 0.0001;
+
+// Keeps a sprite's z in step with how far down the screen it stands, so nearer
+// sprites are drawn over farther ones within the same layer.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct YSort {
+    pub layer: f32,
+}
+
+impl YSort {
+    pub fn new(layer: f32) -> Self {
+        YSort { layer }
+    }
+}
+
+pub struct DepthPlugin;
+
+impl Plugin for DepthPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<YSort>().add_systems(
+            PostUpdate,
+            y_sort.before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+fn y_sort(board: Board, mut sorted_q: Query<(&YSort, &mut Transform), Changed<Transform>>) {
+    for (y_sort, mut transform) in sorted_q.iter_mut() {
+        let pos = transform.translation.truncate();
+        // Sort by where the sprite stands on the ground, so raised hexes don't
+        // push it behind the row it's actually in.
+        let z = y_sort.layer - (pos.y - board.height_at(pos)) * DEPTH_PER_UNIT;
+        if transform.translation.z != z {
+            transform.translation.z = z;
+        }
+    }
+}
@@ -0,0 +1,566 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use hexx::Hex;
+
+use crate::{
+    archetypes::UnitArchetypes,
+    components::{BaseHex, HexTile, Hovered},
+    events::{NewMatch, RebuildBoard},
+    helpers::board::Board,
+    map::{MapData, MapUnit, SpawnPoint, Terrain},
+    objectives::ObjectiveKind,
+    replay::ReplayPlayback,
+    resources::CursorPos,
+    startup::new_seed,
+    states::{AppState, PlayerState},
+    tiles::layers::{LayerAppExt, LayerAppearance},
+};
+
+const MAX_BRUSH_RADIUS: u32 = 4;
+const MAX_ELEVATION: u32 = 4;
+const MAX_FACTION: u32 = 3;
+const HOLD_HEX_TURNS: u32 = 3;
+
+const PALETTE_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const PALETTE_BUTTON_SELECTED: Color = Color::rgb(0.35, 0.55, 0.35);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditorTool {
+    Terrain(Terrain),
+    AddHex,
+    RemoveHex,
+    Raise,
+    Lower,
+    Unit(String),
+    DeleteUnit,
+    SpawnPoint,
+    HoldObjective,
+}
+
+impl EditorTool {
+    fn label(&self) -> String {
+        match self {
+            EditorTool::Terrain(terrain) => format!("{:?}", terrain),
+            EditorTool::AddHex => String::from("Add hex"),
+            EditorTool::RemoveHex => String::from("Remove hex"),
+            EditorTool::Raise => String::from("Raise"),
+            EditorTool::Lower => String::from("Lower"),
+            EditorTool::Unit(archetype) => format!("Unit: {}", archetype),
+            EditorTool::DeleteUnit => String::from("Delete unit"),
+            EditorTool::SpawnPoint => String::from("Spawn point"),
+            EditorTool::HoldObjective => String::from("Hold objective"),
+        }
+    }
+
+    // Brush tools paint every hex under the brush while the button is held.
+    // The others act once per click, on the hex under the cursor.
+    fn uses_brush(&self) -> bool {
+        matches!(
+            self,
+            EditorTool::Terrain(_)
+                | EditorTool::AddHex
+                | EditorTool::RemoveHex
+                | EditorTool::Raise
+                | EditorTool::Lower
+        )
+    }
+}
+
+#[derive(Resource)]
+pub struct EditorSettings {
+    pub tool: EditorTool,
+    pub brush_radius: u32,
+    // Faction of placed units and spawn points.
+    pub faction: u32,
+    pub message: String,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        EditorSettings {
+            tool: EditorTool::Terrain(Terrain::Grass),
+            brush_radius: 0,
+            faction: 0,
+            message: String::new(),
+        }
+    }
+}
+
+// Snapshots of the map from before each edit.
+#[derive(Resource, Default)]
+pub struct EditorHistory {
+    pub undo: Vec<MapData>,
+    pub redo: Vec<MapData>,
+}
+
+#[derive(Component)]
+pub struct SpawnPointMarker;
+
+#[derive(Component)]
+pub struct ObjectiveMarker;
+
+#[derive(Component)]
+pub struct EditorPalette;
+
+#[derive(Component)]
+pub struct PaletteButton(pub EditorTool);
+
+#[derive(Component)]
+pub struct EditorStatusText;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditorSettings>()
+            .init_resource::<EditorHistory>()
+            .register_layer::<SpawnPointMarker>(
+                "SpawnPointMarker",
+                2.2,
+                LayerAppearance::Color(Color::rgba(0.2, 0.6, 1.0, 0.5)),
+            )
+            .register_layer::<ObjectiveMarker>(
+                "ObjectiveMarker",
+                2.3,
+                LayerAppearance::Color(Color::rgba(1.0, 0.8, 0.1, 0.5)),
+            )
+            .add_systems(
+                Update,
+                toggle_editor.run_if(not(resource_exists::<ReplayPlayback>())),
+            )
+            .add_systems(OnEnter(AppState::Editor), (enter_editor, spawn_palette))
+            .add_systems(OnExit(AppState::Editor), exit_editor)
+            .add_systems(
+                Update,
+                rebuild_board
+                    .run_if(in_state(AppState::Editor))
+                    .run_if(resource_changed::<MapData>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    mark_editor_tiles,
+                    select_palette_tool,
+                    editor_shortcuts,
+                    paint,
+                    update_brush_hover,
+                    update_palette,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::Editor)),
+            );
+    }
+}
+
+fn toggle_editor(
+    keyboard_input: Res<Input<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+    match state.get() {
+        AppState::InGame | AppState::GameOver => next_state.set(AppState::Editor),
+        AppState::Editor => next_state.set(AppState::InGame),
+        AppState::LoadingMap | AppState::Lobby => (),
+    }
+}
+
+fn enter_editor(mut map: ResMut<MapData>, mut next_player_state: ResMut<NextState<PlayerState>>) {
+    next_player_state.set(PlayerState::Idle);
+    // Rebuild straight away, so the board shows the map as authored rather
+    // than the state of the match.
+    map.set_changed();
+}
+
+fn exit_editor(
+    mut commands: Commands,
+    palette_q: Query<Entity, With<EditorPalette>>,
+    tile_q: Query<Entity, With<BaseHex>>,
+    mut ev_new_match: EventWriter<NewMatch>,
+) {
+    ev_new_match.send(NewMatch { seed: new_seed() });
+    for entity in palette_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in tile_q.iter() {
+        commands
+            .entity(entity)
+            .remove::<SpawnPointMarker>()
+            .remove::<ObjectiveMarker>()
+            .remove::<Hovered>();
+    }
+}
+
+// Edits only redraw the board. The match starts over once, when the editor is
+// left, so it's played on the map as it was left.
+fn rebuild_board(mut ev_rebuild_board: EventWriter<RebuildBoard>) {
+    ev_rebuild_board.send(RebuildBoard);
+}
+
+fn mark_editor_tiles(
+    mut commands: Commands,
+    tile_q: Query<(Entity, &HexTile), Added<BaseHex>>,
+    map: Res<MapData>,
+) {
+    for (entity, hex_tile) in tile_q.iter() {
+        let hex = (hex_tile.0.x, hex_tile.0.y);
+        if map.spawn_points.iter().any(|spawn| spawn.hex == hex) {
+            commands.entity(entity).insert(SpawnPointMarker);
+        }
+        if map.objectives.iter().any(|objective| {
+            matches!(objective, ObjectiveKind::HoldHex { hex: held, .. } if *held == hex)
+        }) {
+            commands.entity(entity).insert(ObjectiveMarker);
+        }
+    }
+}
+
+fn spawn_palette(mut commands: Commands, archetypes: Res<UnitArchetypes>) {
+    let mut tools: Vec<EditorTool> = Terrain::ALL.into_iter().map(EditorTool::Terrain).collect();
+    tools.extend([
+        EditorTool::AddHex,
+        EditorTool::RemoveHex,
+        EditorTool::Raise,
+        EditorTool::Lower,
+    ]);
+    let mut names: Vec<&String> = archetypes.0.keys().collect();
+    names.sort();
+    tools.extend(names.into_iter().map(|name| EditorTool::Unit(name.clone())));
+    tools.extend([
+        EditorTool::DeleteUnit,
+        EditorTool::SpawnPoint,
+        EditorTool::HoldObjective,
+    ]);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(12.0),
+                    left: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(6.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..default()
+            },
+            // Lets painting tell when the cursor is over the panel.
+            Interaction::default(),
+            EditorPalette,
+            Name::new("Editor Palette"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 16.0,
+                        ..default()
+                    },
+                ),
+                EditorStatusText,
+            ));
+            for tool in tools {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                padding: UiRect::axes(Val::Px(8.0), Val::Px(3.0)),
+                                ..default()
+                            },
+                            background_color: PALETTE_BUTTON.into(),
+                            ..default()
+                        },
+                        Name::new(tool.label()),
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            tool.label(),
+                            TextStyle {
+                                font_size: 16.0,
+                                ..default()
+                            },
+                        ));
+                    })
+                    .insert(PaletteButton(tool));
+            }
+        });
+}
+
+fn select_palette_tool(
+    button_q: Query<(&Interaction, &PaletteButton), Changed<Interaction>>,
+    mut settings: ResMut<EditorSettings>,
+) {
+    for (interaction, button) in button_q.iter() {
+        if *interaction == Interaction::Pressed {
+            settings.tool = button.0.clone();
+        }
+    }
+}
+
+fn update_palette(
+    settings: Res<EditorSettings>,
+    history: Res<EditorHistory>,
+    mut button_q: Query<(&PaletteButton, &mut BackgroundColor)>,
+    mut text_q: Query<&mut Text, With<EditorStatusText>>,
+) {
+    if !settings.is_changed() && !history.is_changed() {
+        return;
+    }
+    for (button, mut color) in button_q.iter_mut() {
+        *color = if button.0 == settings.tool {
+            PALETTE_BUTTON_SELECTED.into()
+        } else {
+            PALETTE_BUTTON.into()
+        };
+    }
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = format!(
+            "Map editor (F2 to play)\nBrush: {} ([ and ])\nFaction: {} (Tab)\nUndo {} / Redo {} (Ctrl+Z, Ctrl+Y)\nCtrl+S to save\n{}",
+            settings.brush_radius,
+            settings.faction,
+            history.undo.len(),
+            history.redo.len(),
+            settings.message
+        );
+    }
+}
+
+fn editor_shortcuts(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<EditorSettings>,
+    mut history: ResMut<EditorHistory>,
+    mut map: ResMut<MapData>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        settings.faction = (settings.faction + 1) % (MAX_FACTION + 1);
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketRight) {
+        settings.brush_radius = (settings.brush_radius + 1).min(MAX_BRUSH_RADIUS);
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+        settings.brush_radius = settings.brush_radius.saturating_sub(1);
+    }
+    if !ctrl {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Z) {
+        if let Some(previous) = history.undo.pop() {
+            history.redo.push(std::mem::replace(&mut *map, previous));
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Y) {
+        if let Some(next) = history.redo.pop() {
+            history.undo.push(std::mem::replace(&mut *map, next));
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::S) {
+        // Saving only moves the map to where it was saved, which doesn't need
+        // the board rebuilt.
+        let map = map.bypass_change_detection();
+        settings.message = match map.save_as_user_map() {
+            Some(path) => format!("Saved {}", path),
+            None => String::from("Could not save the map"),
+        };
+    }
+}
+
+fn cursor_hex(board: &Board, cursor_pos: &CursorPos) -> Hex {
+    board.world_pos_to_hex(cursor_pos.0)
+}
+
+fn brush_hexes(settings: &EditorSettings, center: Hex) -> Vec<Hex> {
+    if settings.tool.uses_brush() {
+        center.range(settings.brush_radius).collect()
+    } else {
+        vec![center]
+    }
+}
+
+// A press of the mouse button, and everything painted until it's released.
+#[derive(Default)]
+struct Stroke {
+    active: bool,
+    // The map as it was before the stroke, until the stroke first changes it.
+    start: Option<MapData>,
+}
+
+type PaletteUi = Or<(With<EditorPalette>, With<PaletteButton>)>;
+
+// The left mouse button, as far as painting goes: presses on the palette don't
+// reach the board.
+#[derive(SystemParam)]
+struct PaintButton<'w, 's> {
+    input: Res<'w, Input<MouseButton>>,
+    ui_q: Query<'w, 's, &'static Interaction, PaletteUi>,
+}
+
+impl PaintButton<'_, '_> {
+    fn over_palette(&self) -> bool {
+        self.ui_q
+            .iter()
+            .any(|interaction| *interaction != Interaction::None)
+    }
+}
+
+fn paint(
+    mouse: PaintButton,
+    cursor_pos: Res<CursorPos>,
+    board: Board,
+    settings: Res<EditorSettings>,
+    mut history: ResMut<EditorHistory>,
+    mut map: ResMut<MapData>,
+    mut stroke: Local<Stroke>,
+) {
+    if mouse.input.just_pressed(MouseButton::Left) {
+        if mouse.over_palette() {
+            return;
+        }
+        *stroke = Stroke {
+            active: true,
+            start: Some(map.clone()),
+        };
+    } else if !mouse.input.pressed(MouseButton::Left) || !settings.tool.uses_brush() {
+        stroke.active = false;
+    }
+    if !stroke.active {
+        return;
+    }
+
+    let hexes = brush_hexes(&settings, cursor_hex(&board, &cursor_pos));
+    if !apply_tool(map.bypass_change_detection(), &settings, &hexes) {
+        return;
+    }
+    map.set_changed();
+    // Only the first change of a stroke is recorded, so a whole stroke undoes
+    // in one go.
+    if let Some(start) = stroke.start.take() {
+        history.undo.push(start);
+        history.redo.clear();
+    }
+}
+
+// Applies the current tool to `hexes`, returning whether the map changed.
+fn apply_tool(map: &mut MapData, settings: &EditorSettings, hexes: &[Hex]) -> bool {
+    let existing = map.all_hexes();
+    let mut changed = false;
+    for hex in hexes.iter().copied() {
+        let key = (hex.x, hex.y);
+        let exists = existing.contains(&hex);
+        match &settings.tool {
+            EditorTool::Terrain(terrain) if exists => {
+                let previous = if *terrain == Terrain::Grass {
+                    map.terrain.remove(&key)
+                } else {
+                    map.terrain.insert(key, *terrain)
+                };
+                changed |= previous.unwrap_or_default() != *terrain;
+            }
+            EditorTool::AddHex if !exists => {
+                map.hexes.push(key);
+                changed = true;
+            }
+            EditorTool::RemoveHex if exists => {
+                // Hexes filled in by the radius have to be listed one by one
+                // before any of them can be taken out.
+                if map.radius.is_some() {
+                    map.hexes = existing.iter().map(|hex| (hex.x, hex.y)).collect();
+                    map.radius = None;
+                }
+                map.hexes.retain(|x| *x != key);
+                map.elevation.remove(&key);
+                map.terrain.remove(&key);
+                map.units.retain(|unit| unit.hex != key);
+                map.spawn_points.retain(|spawn| spawn.hex != key);
+                map.objectives.retain(
+                    |objective| !matches!(objective, ObjectiveKind::HoldHex { hex, .. } if *hex == key),
+                );
+                changed = true;
+            }
+            EditorTool::Raise if exists => {
+                let level = map.elevation.entry(key).or_default();
+                if *level < MAX_ELEVATION {
+                    *level += 1;
+                    changed = true;
+                }
+            }
+            EditorTool::Lower if exists => {
+                if let Some(level) = map.elevation.get_mut(&key) {
+                    *level -= 1;
+                    if *level == 0 {
+                        map.elevation.remove(&key);
+                    }
+                    changed = true;
+                }
+            }
+            EditorTool::Unit(archetype)
+                if exists && !map.units.iter().any(|unit| unit.hex == key) =>
+            {
+                map.units.push(MapUnit {
+                    archetype: archetype.clone(),
+                    hex: key,
+                    faction: settings.faction,
+                });
+                changed = true;
+            }
+            EditorTool::DeleteUnit => {
+                let count = map.units.len();
+                map.units.retain(|unit| unit.hex != key);
+                changed |= map.units.len() != count;
+            }
+            EditorTool::SpawnPoint if exists => {
+                // Clicking a spawn point again removes it.
+                let count = map.spawn_points.len();
+                map.spawn_points.retain(|spawn| spawn.hex != key);
+                if map.spawn_points.len() == count {
+                    map.spawn_points.push(SpawnPoint {
+                        hex: key,
+                        faction: settings.faction,
+                    });
+                }
+                changed = true;
+            }
+            EditorTool::HoldObjective if exists => {
+                let count = map.objectives.len();
+                map.objectives.retain(
+                    |objective| !matches!(objective, ObjectiveKind::HoldHex { hex, .. } if *hex == key),
+                );
+                if map.objectives.len() == count {
+                    map.objectives.push(ObjectiveKind::HoldHex {
+                        hex: key,
+                        turns: HOLD_HEX_TURNS,
+                    });
+                }
+                changed = true;
+            }
+            _ => (),
+        }
+    }
+    changed
+}
+
+fn update_brush_hover(
+    mut commands: Commands,
+    cursor_pos: Res<CursorPos>,
+    board: Board,
+    settings: Res<EditorSettings>,
+    tile_q: Query<(Entity, &HexTile, Option<&Hovered>), With<BaseHex>>,
+) {
+    let hexes = brush_hexes(&settings, cursor_hex(&board, &cursor_pos));
+    for (entity, hex_tile, hovered) in tile_q.iter() {
+        match (hexes.contains(&hex_tile.0), hovered.is_some()) {
+            (true, false) => {
+                commands.entity(entity).insert(Hovered);
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<Hovered>();
+            }
+            _ => (),
+        }
+    }
+}
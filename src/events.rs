@@ -1,11 +1,72 @@
 use bevy::prelude::*;
-use hexx::{Direction, Hex};
+use hexx::Hex;
+
+use crate::{
+    components::{Faction, UnitId},
+    game_command::GameCommand,
+    rules::Rejection,
+};
 
 #[derive(Event)]
 pub struct MapLoaded;
 
+// Throws away the board and starts a new match on the current map.
+#[derive(Event)]
+pub struct NewMatch {
+    pub seed: u64,
+}
+
+// Rebuilds the board from the map, leaving the rest of the match alone.
+#[derive(Event)]
+pub struct RebuildBoard;
+
+// Everything that changes the state of a match goes through here, whether it
+// comes from the player, the AI, the network or a replay. Submitted commands
+// are checked against the rules, and only accepted ones are played.
+#[derive(Event)]
+pub struct CommandSubmitted(pub GameCommand);
+
+// A command the other game in a network match submitted, on behalf of one of
+// the factions it plays.
+#[derive(Event)]
+pub struct CommandReceived {
+    pub faction: Faction,
+    pub command: GameCommand,
+}
+
+#[derive(Event)]
+pub struct CommandAccepted(pub GameCommand);
+
+#[derive(Event)]
+pub struct CommandRejected {
+    pub command: GameCommand,
+    pub reason: Rejection,
+}
+
+#[derive(Event)]
+pub struct TurnStarted {
+    pub turn_number: i32,
+}
+
+// One faction finished its turn and handed over to the next.
+#[derive(Event)]
+pub struct TurnPassed {
+    pub from: Faction,
+    pub to: Faction,
+}
+
+// The faction that won the match.
+#[derive(Event)]
+pub struct VictoryAchieved(pub Faction);
+
+// A faction that lost the match.
 #[derive(Event)]
-pub struct TurnButtonPressed;
+pub struct DefeatSuffered(pub Faction);
+
+// The factions that completed their objectives at the same time, and so tied
+// the match.
+#[derive(Event)]
+pub struct MatchDrawn(pub Vec<Faction>);
 
 #[derive(Event)]
 pub struct MouseClicked(pub Vec2);
@@ -23,10 +84,7 @@ pub struct NewTileClicked(pub Hex);
 pub struct UnitSelected(pub Entity);
 
 #[derive(Event)]
-pub struct UnitDeselected(pub Entity);
-
-#[derive(Event)]
-pub struct HexDoubleClicked(pub Hex);
+pub struct UnitDeselected;
 
 #[derive(Event)]
 pub struct MoveTargetConfirmed {
@@ -35,27 +93,84 @@ pub struct MoveTargetConfirmed {
     pub to: Hex,
 }
 
+#[derive(Event)]
+pub struct UnitArrived {
+    pub unit: Entity,
+    pub hex: Hex,
+    pub end_of_path: bool,
+}
+
 #[derive(Event)]
 pub struct ClearLastClicked;
 
 #[derive(Event)]
-pub struct ClickedOutsideActivationRange(pub Hex);
+pub struct ClickedOutsideActivationRange;
+
+#[derive(Event)]
+pub struct UnitAttacked {
+    pub attacker: Entity,
+    pub target: Entity,
+}
+
+#[derive(Event)]
+pub struct DamageDealt {
+    pub target: Entity,
+    pub amount: i32,
+}
+
+#[derive(Event)]
+pub struct UnitHealed {
+    pub target: Entity,
+    pub amount: i32,
+}
+
+// A unit was bought on turn `turn_number`, and is to be placed on `hex`.
+#[derive(Event)]
+pub struct UnitRecruited {
+    pub unit: UnitId,
+    pub archetype: String,
+    pub faction: Faction,
+    pub hex: Hex,
+    pub turn_number: i32,
+}
+
+#[derive(Event)]
+pub struct UnitHurt(pub Entity);
+
+#[derive(Event)]
+pub struct UnitDied(pub Entity);
 
 pub struct EventsPlugin;
 
 impl Plugin for EventsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<TurnButtonPressed>()
+        app.add_event::<TurnStarted>()
+            .add_event::<TurnPassed>()
+            .add_event::<VictoryAchieved>()
+            .add_event::<DefeatSuffered>()
+            .add_event::<MatchDrawn>()
             .add_event::<MapLoaded>()
+            .add_event::<NewMatch>()
+            .add_event::<RebuildBoard>()
+            .add_event::<CommandSubmitted>()
+            .add_event::<CommandReceived>()
+            .add_event::<CommandAccepted>()
+            .add_event::<CommandRejected>()
             .add_event::<MouseClicked>()
             .add_event::<MouseClickedHex>()
             .add_event::<NewTileClicked>()
             .add_event::<UnitSelected>()
             .add_event::<UnitDeselected>()
             .add_event::<MoveTargetConfirmed>()
-            .add_event::<HexDoubleClicked>()
+            .add_event::<UnitArrived>()
             .add_event::<ClearLastClicked>()
             .add_event::<ClickedOutsideActivationRange>()
+            .add_event::<UnitAttacked>()
+            .add_event::<DamageDealt>()
+            .add_event::<UnitHealed>()
+            .add_event::<UnitRecruited>()
+            .add_event::<UnitHurt>()
+            .add_event::<UnitDied>()
             .add_event::<MouseEnteredHex>();
     }
 }
@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+use hexx::{Direction, Hex};
+
+use crate::{
+    abilities::unit_pos,
+    animation::UnitAnimation,
+    components::{BoardLoc, Facing, Moving, Selected, Unit, UnitId},
+    constants::{FLANK_ATTACK_BONUS, REAR_ATTACK_BONUS},
+    events::CommandSubmitted,
+    game_command::GameCommand,
+    helpers::board::Board,
+    resources::{CursorPos, MapLayout},
+    states::PlayerState,
+};
+
+type SelectedUnit = (With<Selected>, With<Unit>);
+// A new marker has to be pointed the right way too.
+type FacingChanged = Or<(Changed<Facing>, Changed<Children>)>;
+
+// How far from the unit's center the facing marker sits, as a share of the way
+// to the next hex.
+const MARKER_OFFSET: f32 = 0.4;
+const MARKER_SIZE: f32 = 4.0;
+
+// Where an attack comes from, as seen by the unit it hits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttackAngle {
+    Front,
+    Flank,
+    Rear,
+}
+
+impl AttackAngle {
+    pub fn bonus(&self) -> i32 {
+        match self {
+            AttackAngle::Front => 0,
+            AttackAngle::Flank => FLANK_ATTACK_BONUS,
+            AttackAngle::Rear => REAR_ATTACK_BONUS,
+        }
+    }
+}
+
+// Facing is written as an index into `Direction::ALL_DIRECTIONS` in commands
+// and state changes, the same way hexes are written as `(x, y)`.
+pub fn direction_index(direction: Direction) -> u8 {
+    Direction::ALL_DIRECTIONS
+        .iter()
+        .position(|x| *x == direction)
+        .unwrap_or_default() as u8
+}
+
+pub fn direction_from_index(index: u8) -> Direction {
+    Direction::ALL_DIRECTIONS[index as usize % 6]
+}
+
+// The direction from `from` that points most closely at `to`.
+pub fn facing_towards(from: Hex, to: Hex) -> Option<Direction> {
+    if from == to {
+        return None;
+    }
+    let towards = (unit_pos(to) - unit_pos(from)).normalize();
+    Direction::ALL_DIRECTIONS.into_iter().max_by(|a, b| {
+        let a = (unit_pos(from.neighbor(*a)) - unit_pos(from)).normalize();
+        let b = (unit_pos(from.neighbor(*b)) - unit_pos(from)).normalize();
+        towards.dot(a).total_cmp(&towards.dot(b))
+    })
+}
+
+// The three directions in front of a unit are its front, the two behind those
+// its flanks, and the one straight back its rear.
+pub fn attack_angle(target: Hex, facing: Direction, attacker: Hex) -> AttackAngle {
+    let Some(from) = facing_towards(target, attacker) else {
+        return AttackAngle::Front;
+    };
+    let turns = (direction_index(from) as i32 - direction_index(facing) as i32).rem_euclid(6);
+    match turns {
+        0 | 1 | 5 => AttackAngle::Front,
+        2 | 4 => AttackAngle::Flank,
+        _ => AttackAngle::Rear,
+    }
+}
+
+// Units start out looking towards `center`, the middle of the board.
+pub fn starting_facing(hex: Hex, center: Hex) -> Direction {
+    facing_towards(hex, center).unwrap_or_default()
+}
+
+#[derive(Component)]
+pub struct FacingMarker;
+
+pub struct FacingPlugin;
+
+impl Plugin for FacingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Facing>()
+            .add_systems(
+                Update,
+                (face_moving_direction, spawn_facing_markers, update_facing).chain(),
+            )
+            .add_systems(
+                Update,
+                turn_to_face_cursor.run_if(in_state(PlayerState::UnitSelected)),
+            );
+    }
+}
+
+fn face_moving_direction(mut unit_q: Query<(&Moving, &mut Facing), Changed<Moving>>) {
+    for (moving, mut facing) in unit_q.iter_mut() {
+        facing.set_if_neq(Facing(moving.direction));
+    }
+}
+
+// Turns the selected unit towards the hex under the cursor.
+fn turn_to_face_cursor(
+    keyboard_input: Res<Input<KeyCode>>,
+    cursor_pos: Res<CursorPos>,
+    board: Board,
+    unit_q: Query<(&UnitId, &BoardLoc), SelectedUnit>,
+    mut ev_command_submitted: EventWriter<CommandSubmitted>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::R) {
+        return;
+    }
+    let Ok((unit_id, board_loc)) = unit_q.get_single() else {
+        return;
+    };
+    let hex = board.world_pos_to_hex(cursor_pos.0);
+    if hex != board_loc.hex {
+        ev_command_submitted.send(CommandSubmitted(GameCommand::Face {
+            unit: *unit_id,
+            towards: (hex.x, hex.y),
+        }));
+    }
+}
+
+fn spawn_facing_markers(mut commands: Commands, unit_q: Query<Entity, Added<Facing>>) {
+    for unit in unit_q.iter() {
+        let marker = commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgba(1.0, 1.0, 1.0, 0.8),
+                        custom_size: Some(Vec2::splat(MARKER_SIZE)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                FacingMarker,
+                Name::new("Facing Marker"),
+            ))
+            .id();
+        commands.entity(unit).add_child(marker);
+    }
+}
+
+// Points the marker, and the unit's idle sprite, the way the unit faces.
+fn update_facing(
+    layout: Res<MapLayout>,
+    mut unit_q: Query<(&Facing, &Children, &mut UnitAnimation), FacingChanged>,
+    mut marker_q: Query<&mut Transform, With<FacingMarker>>,
+) {
+    for (facing, children, mut animation) in unit_q.iter_mut() {
+        animation.direction = facing.0;
+        let offset = (layout.layout.hex_to_world_pos(Hex::ZERO.neighbor(facing.0))
+            - layout.layout.hex_to_world_pos(Hex::ZERO))
+            * MARKER_OFFSET;
+        for child in children.iter() {
+            if let Ok(mut transform) = marker_q.get_mut(*child) {
+                transform.translation = offset.extend(0.1);
+            }
+        }
+    }
+}
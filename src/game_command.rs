@@ -0,0 +1,515 @@
+use std::collections::VecDeque;
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use hexx::Hex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    abilities::{Abilities, AbilityCooldowns, AbilityDefs},
+    archetypes::UnitArchetypes,
+    components::{
+        ActionPoints, Attack, BoardLoc, Defense, Facing, Faction, MoveRange, MovementSpent, Moving,
+        Recruited, Selected, Skirmisher, Unit, UnitId,
+    },
+    events::{
+        CommandAccepted, CommandReceived, CommandRejected, CommandSubmitted, DamageDealt,
+        MoveTargetConfirmed, NewMatch, TurnPassed, TurnStarted, UnitAttacked, UnitHealed,
+        UnitRecruited,
+    },
+    facing::direction_from_index,
+    hot_seat::{Controller, Players},
+    network::{server_runs_rules, NetSession},
+    resources::{Elevation, HexMap, NextUnitId, TerrainMap, Treasury, TurnQueue},
+    rules::{MatchState, Rejection, StateChange, UnitState},
+    startup::MatchSetup,
+    states::AppState,
+    status_effects::StatusEffects,
+    structures::Structures,
+};
+
+// Something a player asked to happen. Hexes are written as `(x, y)` axial
+// coordinates, like in map files.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum GameCommand {
+    SelectUnit(UnitId),
+    Move {
+        unit: UnitId,
+        to: (i32, i32),
+    },
+    UseAbility {
+        unit: UnitId,
+        ability: String,
+        target: (i32, i32),
+    },
+    // Hits `target` with the first of the unit's abilities that can.
+    Attack {
+        unit: UnitId,
+        target: UnitId,
+    },
+    // The unit is done for the turn, and gives up its action points.
+    Wait(UnitId),
+    // Turns the unit towards a hex, without moving it.
+    Face {
+        unit: UnitId,
+        towards: (i32, i32),
+    },
+    // Buys a unit, placed on `at` next to one of the faction's recruiting
+    // structures.
+    Recruit {
+        archetype: String,
+        at: (i32, i32),
+    },
+    EndTurn,
+}
+
+pub struct GameCommandPlugin;
+
+impl Plugin for GameCommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingChanges>()
+            .add_systems(
+                Update,
+                clear_pending_changes
+                    .in_set(MatchSetup)
+                    .run_if(on_event::<NewMatch>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    validate_commands,
+                    execute_commands,
+                    apply_changes.run_if(in_state(AppState::InGame)),
+                )
+                    .chain(),
+            );
+    }
+}
+
+type RulesUnit = (
+    &'static UnitId,
+    &'static Faction,
+    &'static BoardLoc,
+    &'static Unit,
+    (&'static MoveRange, &'static Attack, &'static Defense),
+    (&'static ActionPoints, &'static MovementSpent),
+    &'static Abilities,
+    &'static AbilityCooldowns,
+    &'static StatusEffects,
+    (
+        &'static Facing,
+        Option<&'static Skirmisher>,
+        Option<&'static Recruited>,
+    ),
+);
+
+// The board as the rules see it, so commands are checked the same way here as
+// on a dedicated server.
+#[derive(SystemParam)]
+pub struct BoardState<'w, 's> {
+    hex_map: Res<'w, HexMap>,
+    elevation: Res<'w, Elevation>,
+    terrain: Res<'w, TerrainMap>,
+    structures: Res<'w, Structures>,
+    turn_queue: Res<'w, TurnQueue>,
+    treasury: Res<'w, Treasury>,
+    archetypes: Res<'w, UnitArchetypes>,
+    next_unit_id: Res<'w, NextUnitId>,
+    unit_q: Query<'w, 's, RulesUnit>,
+}
+
+impl<'w, 's> BoardState<'w, 's> {
+    pub fn snapshot(&self) -> MatchState {
+        let mut units: Vec<UnitState> = self
+            .unit_q
+            .iter()
+            .map(
+                |(
+                    id,
+                    faction,
+                    board_loc,
+                    unit,
+                    (move_range, attack, defense),
+                    (action_points, movement_spent),
+                    abilities,
+                    cooldowns,
+                    status_effects,
+                    (facing, skirmisher, recruited),
+                )| UnitState {
+                    id: *id,
+                    faction: *faction,
+                    hex: board_loc.hex,
+                    health: unit.health,
+                    max_health: unit.max_health,
+                    move_range: move_range.0,
+                    attack: attack.0,
+                    defense: defense.0,
+                    action_points: *action_points,
+                    movement_spent: movement_spent.0,
+                    abilities: abilities.0.clone(),
+                    cooldowns: cooldowns.clone(),
+                    status_effects: status_effects.clone(),
+                    skirmisher: skirmisher.is_some(),
+                    facing: facing.0,
+                    recruited_on: recruited.map(|recruited| recruited.0),
+                },
+            )
+            .collect();
+        units.sort_by_key(|unit| unit.id.0);
+        MatchState {
+            hex_map: self.hex_map.clone(),
+            elevation: self.elevation.clone(),
+            terrain: self.terrain.clone(),
+            structures: self.structures.clone(),
+            turn_queue: self.turn_queue.clone(),
+            treasury: self.treasury.clone(),
+            archetypes: self.archetypes.clone(),
+            next_unit_id: self.next_unit_id.0,
+            units,
+        }
+    }
+}
+
+// Changes the rules made to the match, from commands played here or sent by a
+// dedicated server, waiting to be shown on the board a command at a time.
+#[derive(Resource, Default)]
+pub struct PendingChanges(pub VecDeque<Vec<StateChange>>);
+
+// Where commands go once they've been checked.
+#[derive(SystemParam)]
+struct Verdicts<'w> {
+    accepted: EventWriter<'w, CommandAccepted>,
+    rejected: EventWriter<'w, CommandRejected>,
+    pending: ResMut<'w, PendingChanges>,
+    session: Option<Res<'w, NetSession>>,
+}
+
+// Commands submitted here are given on behalf of the faction whose turn it is,
+// as long as that faction is played on this machine. Commands from the other
+// game in a network match are given on behalf of the faction they were sent
+// for, which has to be one it plays. Each command is checked against the board
+// as the ones accepted before it leave it.
+fn validate_commands(
+    mut ev_command_submitted: EventReader<CommandSubmitted>,
+    mut ev_command_received: EventReader<CommandReceived>,
+    board: BoardState,
+    players: Res<Players>,
+    ability_defs: Res<AbilityDefs>,
+    mut verdicts: Verdicts,
+) {
+    if ev_command_submitted.is_empty() && ev_command_received.is_empty() {
+        return;
+    }
+    let submitted: Vec<(Option<Faction>, GameCommand)> = ev_command_submitted
+        .iter()
+        .map(|ev| (None, ev.0.clone()))
+        .chain(
+            ev_command_received
+                .iter()
+                .map(|ev| (Some(ev.faction), ev.command.clone())),
+        )
+        .collect();
+    // A dedicated server plays the commands itself, and sends what changed.
+    let on_server = server_runs_rules(verdicts.session);
+    let mut state = board.snapshot();
+    for (sender, command) in submitted {
+        let (faction, played_by_sender) = match sender {
+            Some(faction) => (faction, players.controller(faction) == Controller::Remote),
+            None => {
+                let active = state.turn_queue.active_faction();
+                (active, players.controller(active) != Controller::Remote)
+            }
+        };
+        let validated = if played_by_sender {
+            state.validate(faction, &command, &ability_defs)
+        } else {
+            Err(Rejection::NotYourTurn)
+        };
+        match validated {
+            Ok(command) => {
+                let changes = state.apply(&command, &ability_defs);
+                if !on_server && !changes.is_empty() {
+                    verdicts.pending.0.push_back(changes);
+                }
+                verdicts.accepted.send(CommandAccepted(command));
+            }
+            Err(reason) => verdicts.rejected.send(CommandRejected { command, reason }),
+        }
+    }
+}
+
+// Selecting a unit is all a command does outside the rules. Everything else
+// reaches the board as the changes the rules made.
+fn execute_commands(
+    mut commands: Commands,
+    mut ev_command_accepted: EventReader<CommandAccepted>,
+    unit_q: Query<(Entity, &UnitId, Option<&Selected>), With<Unit>>,
+) {
+    for ev in ev_command_accepted.iter() {
+        let GameCommand::SelectUnit(id) = ev.0 else {
+            continue;
+        };
+        let Some((unit, _, _)) = unit_q.iter().find(|(_, unit_id, _)| **unit_id == id) else {
+            continue;
+        };
+        for (other, _, selected) in unit_q.iter() {
+            if other != unit && selected.is_some() {
+                commands.entity(other).remove::<Selected>();
+            }
+        }
+        commands.entity(unit).insert(Selected);
+    }
+}
+
+fn clear_pending_changes(mut pending: ResMut<PendingChanges>) {
+    pending.0.clear();
+}
+
+// The match's books, as the changes leave them.
+#[derive(SystemParam)]
+struct Ledger<'w> {
+    turn_queue: ResMut<'w, TurnQueue>,
+    structures: ResMut<'w, Structures>,
+    treasury: ResMut<'w, Treasury>,
+    next_unit_id: ResMut<'w, NextUnitId>,
+}
+
+// What a batch of changes is played back as on the board.
+#[derive(SystemParam)]
+struct ChangeEffects<'w> {
+    move_target_confirmed: EventWriter<'w, MoveTargetConfirmed>,
+    unit_attacked: EventWriter<'w, UnitAttacked>,
+    damage_dealt: EventWriter<'w, DamageDealt>,
+    unit_healed: EventWriter<'w, UnitHealed>,
+    turn_passed: EventWriter<'w, TurnPassed>,
+    turn_started: EventWriter<'w, TurnStarted>,
+    unit_recruited: EventWriter<'w, UnitRecruited>,
+}
+
+type ChangedUnit = (
+    Entity,
+    &'static UnitId,
+    &'static BoardLoc,
+    &'static Unit,
+    &'static mut StatusEffects,
+    (&'static mut ActionPoints, &'static mut MovementSpent),
+    &'static mut AbilityCooldowns,
+);
+
+// Brings the board in line with what the rules say happened, one command's
+// worth of changes at a time. Moves are animated, and health changes go
+// through as damage and healing so units are seen taking hits.
+fn apply_changes(
+    mut pending: ResMut<PendingChanges>,
+    mut ledger: Ledger,
+    mut unit_q: Query<ChangedUnit>,
+    mut facing_q: Query<(&UnitId, &mut Facing)>,
+    moving_q: Query<(), With<Moving>>,
+    mut effects: ChangeEffects,
+) {
+    if !moving_q.is_empty() {
+        return;
+    }
+    let Some(changes) = pending.0.pop_front() else {
+        return;
+    };
+    let entities: HashMap<UnitId, Entity> = unit_q
+        .iter()
+        .map(|(entity, id, ..)| (*id, entity))
+        .collect();
+    // Damage and healing only land later in the frame, so a unit's health can
+    // change more than once in a batch.
+    let mut health: HashMap<UnitId, i32> = unit_q
+        .iter()
+        .map(|(_, id, _, unit, ..)| (*id, unit.health))
+        .collect();
+    for change in changes {
+        match change {
+            StateChange::UnitMoved { unit, to } => {
+                if let Some((entity, _, board_loc, ..)) =
+                    unit_q.iter().find(|(_, id, ..)| **id == unit)
+                {
+                    effects.move_target_confirmed.send(MoveTargetConfirmed {
+                        unit: entity,
+                        from: board_loc.hex,
+                        to: Hex::new(to.0, to.1),
+                    });
+                }
+            }
+            StateChange::UnitAttacked { attacker, target } => {
+                if let (Some(&attacker), Some(&target)) =
+                    (entities.get(&attacker), entities.get(&target))
+                {
+                    effects
+                        .unit_attacked
+                        .send(UnitAttacked { attacker, target });
+                }
+            }
+            StateChange::Health {
+                unit,
+                health: new_health,
+            } => {
+                let (Some(&entity), Some(current)) = (entities.get(&unit), health.get_mut(&unit))
+                else {
+                    continue;
+                };
+                let difference = new_health - *current;
+                *current = new_health;
+                if difference < 0 {
+                    effects.damage_dealt.send(DamageDealt {
+                        target: entity,
+                        amount: -difference,
+                    });
+                } else if difference > 0 {
+                    effects.unit_healed.send(UnitHealed {
+                        target: entity,
+                        amount: difference,
+                    });
+                }
+            }
+            StateChange::StatusEffects {
+                unit,
+                effects: unit_effects,
+            } => {
+                if let Some((.., mut status_effects, _, _)) =
+                    unit_q.iter_mut().find(|(_, id, ..)| **id == unit)
+                {
+                    status_effects.0 = unit_effects;
+                }
+            }
+            StateChange::ActionPoints { unit, current } => {
+                if let Some((.., (mut action_points, _), _)) =
+                    unit_q.iter_mut().find(|(_, id, ..)| **id == unit)
+                {
+                    action_points.current = current;
+                }
+            }
+            StateChange::MovementSpent { unit, spent } => {
+                if let Some((.., (_, mut movement_spent), _)) =
+                    unit_q.iter_mut().find(|(_, id, ..)| **id == unit)
+                {
+                    movement_spent.0 = spent;
+                }
+            }
+            StateChange::Cooldowns { unit, cooldowns } => {
+                if let Some((.., mut unit_cooldowns)) =
+                    unit_q.iter_mut().find(|(_, id, ..)| **id == unit)
+                {
+                    unit_cooldowns.0 = cooldowns.into_iter().collect();
+                }
+            }
+            StateChange::Facing { unit, facing } => {
+                if let Some((_, mut unit_facing)) = facing_q.iter_mut().find(|(id, _)| **id == unit)
+                {
+                    unit_facing.0 = direction_from_index(facing);
+                }
+            }
+            StateChange::StructureCaptured { hex, faction } => {
+                ledger
+                    .structures
+                    .capture(Hex::new(hex.0, hex.1), Faction(faction));
+            }
+            StateChange::Gold { faction, gold } => ledger.treasury.set(Faction(faction), gold),
+            StateChange::UnitRecruited {
+                unit,
+                archetype,
+                faction,
+                hex,
+            } => {
+                ledger.next_unit_id.0 = ledger.next_unit_id.0.max(unit.0 + 1);
+                effects.unit_recruited.send(UnitRecruited {
+                    unit,
+                    archetype,
+                    faction: Faction(faction),
+                    hex: Hex::new(hex.0, hex.1),
+                    turn_number: ledger.turn_queue.turn_number,
+                });
+            }
+            StateChange::TurnPassed {
+                turn_number,
+                faction,
+            } => {
+                let from = ledger.turn_queue.active_faction();
+                let to = Faction(faction);
+                ledger.turn_queue.active = ledger
+                    .turn_queue
+                    .factions
+                    .iter()
+                    .position(|x| *x == to)
+                    .unwrap_or_default();
+                if turn_number != ledger.turn_queue.turn_number {
+                    ledger.turn_queue.turn_number = turn_number;
+                    effects.turn_started.send(TurnStarted { turn_number });
+                }
+                effects.turn_passed.send(TurnPassed { from, to });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hexx::shapes;
+
+    use super::*;
+
+    #[test]
+    fn commands_played_here_reach_the_board_as_changes() {
+        let mut app = App::new();
+        app.add_state::<AppState>()
+            .add_event::<CommandSubmitted>()
+            .add_event::<CommandReceived>()
+            .add_event::<CommandAccepted>()
+            .add_event::<CommandRejected>()
+            .add_event::<NewMatch>()
+            .add_event::<MoveTargetConfirmed>()
+            .add_event::<UnitAttacked>()
+            .add_event::<DamageDealt>()
+            .add_event::<UnitHealed>()
+            .add_event::<TurnPassed>()
+            .add_event::<TurnStarted>()
+            .add_event::<UnitRecruited>()
+            .insert_resource(HexMap(shapes::hexagon(Hex::ZERO, 3).collect()))
+            .init_resource::<Elevation>()
+            .init_resource::<TerrainMap>()
+            .init_resource::<Structures>()
+            .insert_resource(TurnQueue::new(vec![Faction(0), Faction(1)]))
+            .init_resource::<Treasury>()
+            .init_resource::<UnitArchetypes>()
+            .init_resource::<NextUnitId>()
+            .init_resource::<Players>()
+            .init_resource::<AbilityDefs>()
+            .add_plugins(GameCommandPlugin);
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::InGame);
+        let unit = |id, faction, hex| {
+            (
+                (UnitId(id), Faction(faction), BoardLoc { hex }),
+                Unit {
+                    health: 10,
+                    max_health: 10,
+                },
+                (MoveRange(3), Attack(0), Defense(0)),
+                (ActionPoints { current: 2, max: 2 }, MovementSpent(0)),
+                Abilities::default(),
+                AbilityCooldowns::default(),
+                StatusEffects::default(),
+                Facing::default(),
+            )
+        };
+        let ours = app.world.spawn(unit(0, 0, Hex::ZERO)).id();
+        app.world.spawn(unit(1, 1, Hex::new(3, 0)));
+
+        app.world
+            .send_event(CommandSubmitted(GameCommand::Wait(UnitId(0))));
+        app.world.send_event(CommandSubmitted(GameCommand::EndTurn));
+        app.update();
+        app.update();
+        assert_eq!(app.world.get::<ActionPoints>(ours).unwrap().current, 0);
+        assert_eq!(
+            app.world.resource::<TurnQueue>().active_faction(),
+            Faction(1)
+        );
+        assert!(!app.world.resource::<Events<TurnPassed>>().is_empty());
+    }
+}
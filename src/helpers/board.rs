@@ -0,0 +1,50 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use hexx::Hex;
+
+use crate::{
+    constants::ELEVATION_STEP,
+    resources::{Elevation, HexMap, MapLayout},
+};
+
+// Converts between hexes and world positions on the loaded map, raising hexes
+// by their elevation.
+#[derive(SystemParam)]
+pub struct Board<'w> {
+    pub layout: Res<'w, MapLayout>,
+    pub elevation: Res<'w, Elevation>,
+    pub hex_map: Res<'w, HexMap>,
+}
+
+impl<'w> Board<'w> {
+    fn height(&self, level: u32) -> Vec2 {
+        Vec2::Y * level as f32 * ELEVATION_STEP
+    }
+
+    // Where the top of the hex is drawn.
+    pub fn world_pos(&self, hex: Hex) -> Vec2 {
+        self.layout.layout.hex_to_world_pos(hex) + self.height(self.elevation.level(hex))
+    }
+
+    // Finds the hex drawn under `pos`. Raised hexes cover the ones behind them,
+    // so the highest hex found there wins.
+    pub fn world_pos_to_hex(&self, pos: Vec2) -> Hex {
+        (0..=self.elevation.highest())
+            .rev()
+            .map(|level| {
+                let hex = self
+                    .layout
+                    .layout
+                    .world_pos_to_hex(pos - self.height(level));
+                (level, hex)
+            })
+            .find(|(level, hex)| self.elevation.level(*hex) == *level)
+            .map(|(_, hex)| hex)
+            .unwrap_or_else(|| self.layout.layout.world_pos_to_hex(pos))
+    }
+
+    // How high above the ground the hex under `pos` is drawn.
+    pub fn height_at(&self, pos: Vec2) -> f32 {
+        self.height(self.elevation.level(self.world_pos_to_hex(pos)))
+            .y
+    }
+}
@@ -0,0 +1,66 @@
+use std::{fs, path::Path};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+pub const ASSETS_DIR: &str = "assets";
+
+// Reads a RON data file from the assets folder. Data files are small and only
+// read while building the app, so there's no need to go through the AssetServer.
+pub fn load_ron<T: DeserializeOwned>(path: impl AsRef<Path>) -> Option<T> {
+    let full_path = Path::new(ASSETS_DIR).join(path);
+    let contents = match fs::read_to_string(&full_path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            error!("Could not read {}: {}", full_path.display(), err);
+            return None;
+        }
+    };
+    match ron::from_str(&contents) {
+        Ok(data) => Some(data),
+        Err(err) => {
+            error!("Could not parse {}: {}", full_path.display(), err);
+            None
+        }
+    }
+}
+
+// Writes a RON data file into the assets folder, creating its folder if need be,
+// and returns whether it worked.
+pub fn save_ron<T: Serialize>(path: impl AsRef<Path>, data: &T) -> bool {
+    let full_path = Path::new(ASSETS_DIR).join(path);
+    let contents = match ron::ser::to_string_pretty(data, ron::ser::PrettyConfig::default()) {
+        Ok(contents) => contents,
+        Err(err) => {
+            error!("Could not serialize {}: {}", full_path.display(), err);
+            return false;
+        }
+    };
+    let dir = full_path.parent().unwrap_or(Path::new(ASSETS_DIR));
+    match fs::create_dir_all(dir).and_then(|_| fs::write(&full_path, contents)) {
+        Ok(()) => true,
+        Err(err) => {
+            error!("Could not write {}: {}", full_path.display(), err);
+            false
+        }
+    }
+}
+
+// Loads every RON file in a folder of the assets directory.
+pub fn load_ron_dir<T: DeserializeOwned>(dir: impl AsRef<Path>) -> Vec<T> {
+    let full_dir = Path::new(ASSETS_DIR).join(dir.as_ref());
+    let Ok(entries) = fs::read_dir(&full_dir) else {
+        error!("Could not read directory {}", full_dir.display());
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|x| x.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .filter_map(|path| path.strip_prefix(ASSETS_DIR).ok())
+        .filter_map(load_ron)
+        .collect()
+}
@@ -1,2 +1,5 @@
+pub mod board;
 pub mod camera;
+pub mod data;
+pub mod tween;
 pub mod unit;
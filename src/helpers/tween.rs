@@ -0,0 +1,48 @@
+use bevy::reflect::Reflect;
+
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    // Only the ends of a path are eased, so units don't slow down at every hex
+    // they pass through.
+    pub fn for_segment(first: bool, last: bool) -> Easing {
+        match (first, last) {
+            (true, true) => Easing::EaseInOut,
+            (true, false) => Easing::EaseIn,
+            (false, true) => Easing::EaseOut,
+            (false, false) => Easing::Linear,
+        }
+    }
+
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+        }
+    }
+
+    // The quadratic curves peak at twice the linear speed, so eased segments take
+    // twice as long to meet the linear segments at the same speed.
+    pub fn duration_scale(self) -> f32 {
+        match self {
+            Easing::Linear => 1.0,
+            Easing::EaseIn | Easing::EaseOut | Easing::EaseInOut => 2.0,
+        }
+    }
+}
@@ -1,206 +1,514 @@
-use bevy::{math::Vec3Swizzles, prelude::*};
-use hexx::{algorithms::field_of_movement, Hex};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use bevy::{
+    ecs::system::SystemParam, input::common_conditions::input_just_pressed, math::Vec3Swizzles,
+    prelude::*,
+};
+use hexx::Hex;
 
 use crate::{
     components::{
-        Activated, BaseHex, BoardLoc, HexTile, MoveRange, MoveTarget, Moving, Path, Selected, Unit,
+        Activated, BaseHex, BoardLoc, EffectiveStats, Faction, HexTile, MoveTarget, MoveTween,
+        MovementSpent, Moving, Path, PathPreview, Selectable, Selected, Skirmisher, Unit, UnitId,
+        ZoneOfControlStop,
     },
-    constants::LAYOUT,
     events::{
-        ClickedOutsideActivationRange, HexDoubleClicked, MoveTargetConfirmed, NewTileClicked,
+        ClickedOutsideActivationRange, CommandSubmitted, MouseClickedHex, MoveTargetConfirmed,
+        UnitArrived, UnitSelected,
     },
-    resources::HexMap,
+    game_command::GameCommand,
+    helpers::{board::Board, tween::Easing},
+    resources::{AnimationSpeed, Elevation, HexMap},
     states::PlayerState,
+    tiles::layers::{LayerAppExt, LayerAppearance},
 };
 
+type SelectedUnit = (With<Selected>, With<Unit>);
+type OtherUnit = (Without<Selected>, With<Unit>);
+type OrderTile = (
+    Entity,
+    &'static HexTile,
+    Option<&'static Activated>,
+    Option<&'static MoveTarget>,
+);
+type ShownOrder = (With<BaseHex>, Or<(With<MoveTarget>, With<PathPreview>)>);
+
 pub struct UnitPlugin;
 
 impl Plugin for UnitPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.register_type::<Moving>()
             .register_type::<Path>()
-            .add_systems(OnEnter(PlayerState::UnitSelected), add_activated_to_tiles)
+            .register_type::<MoveTween>()
+            .register_layer::<MoveTarget>(
+                "MoveTarget",
+                2.1,
+                LayerAppearance::Texture(String::from("selected-tile.png")),
+            )
+            .register_layer::<PathPreview>(
+                "PathPreview",
+                1.8,
+                LayerAppearance::Color(Color::rgba(1.0, 1.0, 1.0, 0.35)),
+            )
+            .register_layer::<ZoneOfControlStop>(
+                "ZoneOfControlStop",
+                2.2,
+                LayerAppearance::Color(Color::rgba(0.9, 0.2, 0.2, 0.5)),
+            )
+            .add_systems(Update, cycle_animation_speed)
+            .add_systems(
+                Update,
+                add_activated_to_tiles.run_if(on_event::<UnitSelected>()),
+            )
             .add_systems(
                 Update,
                 (
-                    add_move_target_to_tile.run_if(did_not_click_selected_unit),
-                    send_move_target_confirmed_event,
-                    on_move_target_confirmed,
-                    on_clicked_outside_activation_range,
+                    on_hex_clicked,
+                    confirm_on_enter,
+                    cancel_on_right_click.run_if(input_just_pressed(MouseButton::Right)),
                 )
                     .run_if(in_state(PlayerState::UnitSelected)),
             )
+            .add_systems(Update, on_move_target_confirmed)
             .add_systems(
                 Update,
-                (move_along_path, flip_sprite_when_moving)
-                    .run_if(in_state(PlayerState::UnitMoving)),
+                move_along_path.run_if(in_state(PlayerState::UnitMoving)),
             );
     }
 }
 
-fn on_clicked_outside_activation_range(
-    mut ev_new_tile_clicked: EventReader<NewTileClicked>,
-    tiles_q: Query<&HexTile, (With<BaseHex>, Without<Activated>)>,
-    mut ev_clicked_outside: EventWriter<ClickedOutsideActivationRange>,
-) {
-    for ev in ev_new_tile_clicked.iter() {
-        for tile in tiles_q.iter() {
-            if ev.0 == tile.0 {
-                ev_clicked_outside.send(ClickedOutsideActivationRange(ev.0));
-                return;
+// Movement spent stepping between two neighboring hexes. Climbing costs an extra
+// point per level, going down is free.
+pub fn step_cost(from: Hex, to: Hex, elevation: &Elevation) -> u32 {
+    1 + elevation.level(to).saturating_sub(elevation.level(from))
+}
+
+// Movement spent walking `path`, from its first hex to its last.
+pub fn path_cost(path: &[Hex], elevation: &Elevation) -> u32 {
+    path.windows(2)
+        .map(|step| step_cost(step[0], step[1], elevation))
+        .sum()
+}
+
+// Hexes next to enemies. A unit that steps into one has to stop there.
+#[derive(Default, Clone, Debug)]
+pub struct ZoneOfControl(pub HashSet<Hex>);
+
+impl ZoneOfControl {
+    // The zone `faction` has to respect, given where every unit stands.
+    // Skirmishers slip through, so they have none.
+    pub fn against(
+        faction: Faction,
+        skirmisher: bool,
+        units: impl IntoIterator<Item = (Faction, Hex)>,
+    ) -> Self {
+        if skirmisher {
+            return ZoneOfControl::default();
+        }
+        ZoneOfControl(
+            units
+                .into_iter()
+                .filter(|(other, _)| *other != faction)
+                .flat_map(|(_, hex)| hex.all_neighbors())
+                .collect(),
+        )
+    }
+
+    pub fn stops(&self, hex: Hex) -> bool {
+        self.0.contains(&hex)
+    }
+}
+
+pub type ZoneOfControlQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Faction,
+        &'static BoardLoc,
+        Option<&'static Skirmisher>,
+    ),
+    With<Unit>,
+>;
+
+// The zone of control `unit` moves through.
+pub fn zone_of_control(unit: Entity, unit_q: &ZoneOfControlQuery) -> ZoneOfControl {
+    let Ok((_, faction, _, skirmisher)) = unit_q.get(unit) else {
+        return ZoneOfControl::default();
+    };
+    ZoneOfControl::against(
+        *faction,
+        skirmisher.is_some(),
+        unit_q
+            .iter()
+            .map(|(_, faction, board_loc, _)| (*faction, board_loc.hex)),
+    )
+}
+
+// The cheapest way to reach every hex within `budget` of `from`, as the cost to
+// get there and the hex it's reached from. Movement doesn't carry on out of a
+// zone of control, unless it started there.
+fn cheapest_steps(
+    from: Hex,
+    budget: u32,
+    hex_map: &HexMap,
+    elevation: &Elevation,
+    zone_of_control: &ZoneOfControl,
+) -> HashMap<Hex, (u32, Hex)> {
+    let mut reached = HashMap::from([(from, (0u32, from))]);
+    let mut frontier = BinaryHeap::from([Reverse((0u32, from.x, from.y))]);
+    while let Some(Reverse((cost, x, y))) = frontier.pop() {
+        let hex = Hex::new(x, y);
+        if reached.get(&hex).is_some_and(|(best, _)| *best < cost) {
+            continue;
+        }
+        if hex != from && zone_of_control.stops(hex) {
+            continue;
+        }
+        for next in hex.all_neighbors() {
+            if !hex_map.0.contains(&next) {
+                continue;
             }
+            let next_cost = cost.saturating_add(step_cost(hex, next, elevation));
+            if next_cost > budget
+                || reached
+                    .get(&next)
+                    .is_some_and(|(best, _)| *best <= next_cost)
+            {
+                continue;
+            }
+            reached.insert(next, (next_cost, hex));
+            frontier.push(Reverse((next_cost, next.x, next.y)));
         }
     }
+    reached
 }
 
+// Every hex a unit standing on `from` can move to, including the one it's on.
+pub fn movement_range(
+    from: Hex,
+    move_range: u32,
+    hex_map: &HexMap,
+    elevation: &Elevation,
+    zone_of_control: &ZoneOfControl,
+) -> HashSet<Hex> {
+    cheapest_steps(from, move_range, hex_map, elevation, zone_of_control)
+        .into_keys()
+        .collect()
+}
+
+// The cheapest path from `from` to `to`, including both ends.
+pub fn find_path(
+    from: Hex,
+    to: Hex,
+    hex_map: &HexMap,
+    elevation: &Elevation,
+    zone_of_control: &ZoneOfControl,
+) -> Option<Vec<Hex>> {
+    let reached = cheapest_steps(from, u32::MAX, hex_map, elevation, zone_of_control);
+    let mut path = vec![to];
+    let mut current = to;
+    while current != from {
+        current = reached.get(&current)?.1;
+        path.push(current);
+    }
+    path.reverse();
+    Some(path)
+}
+
+// Shows where the unit that was just selected can go, replacing whatever the
+// previous selection showed.
 fn add_activated_to_tiles(
     mut commands: Commands,
-    unit_q: Query<(&BoardLoc, &MoveRange), (With<Selected>, With<Unit>)>,
+    mut ev_unit_selected: EventReader<UnitSelected>,
+    unit_q: Query<(&BoardLoc, &EffectiveStats, &MovementSpent), With<Unit>>,
+    zone_of_control_q: ZoneOfControlQuery,
     tile_q: Query<(Entity, &HexTile), With<BaseHex>>,
     hex_map: Res<HexMap>,
+    elevation: Res<Elevation>,
 ) {
-    if let Ok((board_loc, move_range)) = unit_q.get_single() {
-        let mut result = field_of_movement(board_loc.hex, move_range.0, |_h| Some(0));
-        result.insert(board_loc.hex);
-        for hex_result in result.iter() {
-            if let Some(tile_pos) = hex_map.0.get(hex_result) {
-                for (tile_entity, hex_tile) in tile_q.iter() {
-                    if hex_tile.0 == *tile_pos {
-                        commands.entity(tile_entity).insert(Activated);
-                    }
-                }
-            }
+    let Some(ev) = ev_unit_selected.iter().last() else {
+        return;
+    };
+    let Ok((board_loc, stats, spent)) = unit_q.get(ev.0) else {
+        return;
+    };
+    let result = movement_range(
+        board_loc.hex,
+        stats.move_range.saturating_sub(spent.0),
+        &hex_map,
+        &elevation,
+        &zone_of_control(ev.0, &zone_of_control_q),
+    );
+    for (tile_entity, hex_tile) in tile_q.iter() {
+        let mut tile = commands.entity(tile_entity);
+        tile.remove::<MoveTarget>()
+            .remove::<PathPreview>()
+            .remove::<ZoneOfControlStop>();
+        if result.contains(&hex_tile.0) {
+            tile.insert(Activated);
+        } else {
+            tile.remove::<Activated>();
+        }
+        if hex_tile.0 == board_loc.hex {
+            tile.insert(Selected);
+        } else {
+            tile.remove::<Selected>();
         }
     }
 }
 
-fn add_move_target_to_tile(
+#[derive(SystemParam)]
+struct Terrain<'w, 's> {
+    hex_map: Res<'w, HexMap>,
+    elevation: Res<'w, Elevation>,
+    zone_of_control_q: ZoneOfControlQuery<'w, 's>,
+}
+
+// What a click on the board can turn into.
+#[derive(SystemParam)]
+struct Orders<'w> {
+    command_submitted: EventWriter<'w, CommandSubmitted>,
+    clicked_outside: EventWriter<'w, ClickedOutsideActivationRange>,
+}
+
+// Clicking another of the player's units selects it instead. Otherwise the
+// first click on a hex shows the order, and clicking it again gives it.
+fn on_hex_clicked(
     mut commands: Commands,
-    mut ev_new_tile_clicked: EventReader<NewTileClicked>,
-    tile_q: Query<(Entity, &HexTile), (With<BaseHex>, With<Activated>, Without<Selected>)>,
-    move_target_q: Query<Entity, (With<BaseHex>, With<Activated>, With<MoveTarget>)>,
+    mut ev_mouse_clicked_hex: EventReader<MouseClickedHex>,
+    selected_q: Query<(Entity, &UnitId, &BoardLoc), SelectedUnit>,
+    other_q: Query<(&UnitId, &BoardLoc, Option<&Selectable>), OtherUnit>,
+    tile_q: Query<OrderTile, With<BaseHex>>,
+    terrain: Terrain,
+    mut orders: Orders,
 ) {
-    for ev in ev_new_tile_clicked.iter() {
-        if let Some((entity, _)) = tile_q.iter().find(|(_, hex_tile)| hex_tile.0 == ev.0) {
-            for existing_move_target in move_target_q.iter() {
-                commands.entity(existing_move_target).remove::<MoveTarget>();
-            }
-            commands
-                .entity(entity)
-                .insert(MoveTarget(ev.0))
-                .insert(Selected);
+    let Some(ev) = ev_mouse_clicked_hex.iter().last() else {
+        return;
+    };
+    let Ok((unit, unit_id, board_loc)) = selected_q.get_single() else {
+        return;
+    };
+    let occupant = other_q.iter().find(|(_, loc, _)| loc.hex == ev.0);
+    if let Some((other_id, _, Some(_))) = occupant {
+        orders
+            .command_submitted
+            .send(CommandSubmitted(GameCommand::SelectUnit(*other_id)));
+        return;
+    }
+    if ev.0 == board_loc.hex {
+        return;
+    }
+    let Some((_, _, activated, _)) = tile_q.iter().find(|(_, tile, _, _)| tile.0 == ev.0) else {
+        return;
+    };
+    if tile_q
+        .iter()
+        .any(|(_, _, _, target)| target.is_some_and(|x| x.0 == ev.0))
+    {
+        orders
+            .command_submitted
+            .send(CommandSubmitted(order(*unit_id, ev.0, occupant)));
+        return;
+    }
+    if occupant.is_none() && activated.is_none() {
+        orders.clicked_outside.send(ClickedOutsideActivationRange);
+        return;
+    }
+    // Only the hexes passed through are marked. Attacks don't move the unit.
+    let zone_of_control = zone_of_control(unit, &terrain.zone_of_control_q);
+    let path: Vec<Hex> = match occupant {
+        Some(_) => Vec::new(),
+        None => find_path(
+            board_loc.hex,
+            ev.0,
+            &terrain.hex_map,
+            &terrain.elevation,
+            &zone_of_control,
+        )
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|hex| *hex != board_loc.hex && *hex != ev.0)
+        .collect(),
+    };
+    // Ending up next to an enemy pins the unit down until its next turn.
+    let stops = occupant.is_none() && zone_of_control.stops(ev.0);
+    for (entity, tile, _, target) in tile_q.iter() {
+        let mut tile_commands = commands.entity(entity);
+        if tile.0 == ev.0 {
+            tile_commands.insert(MoveTarget(ev.0));
+        } else if target.is_some() {
+            tile_commands.remove::<MoveTarget>();
+        }
+        if path.contains(&tile.0) {
+            tile_commands.insert(PathPreview);
+        } else {
+            tile_commands.remove::<PathPreview>();
+        }
+        if stops && tile.0 == ev.0 {
+            tile_commands.insert(ZoneOfControlStop);
+        } else {
+            tile_commands.remove::<ZoneOfControlStop>();
         }
     }
 }
 
-fn send_move_target_confirmed_event(
-    mut ev_double_clicked: EventReader<HexDoubleClicked>,
-    tile_q: Query<&HexTile, (With<BaseHex>, With<MoveTarget>)>,
-    unit_q: Query<(Entity, &BoardLoc), (With<Selected>, With<Unit>)>,
-    mut move_target_ev: EventWriter<MoveTargetConfirmed>,
+// Moves to an empty hex, or attacks whoever is standing on it.
+fn order(
+    unit: UnitId,
+    hex: Hex,
+    occupant: Option<(&UnitId, &BoardLoc, Option<&Selectable>)>,
+) -> GameCommand {
+    match occupant {
+        Some((target, _, _)) => GameCommand::Attack {
+            unit,
+            target: *target,
+        },
+        None => GameCommand::Move {
+            unit,
+            to: (hex.x, hex.y),
+        },
+    }
+}
+
+fn confirm_on_enter(
+    keyboard_input: Res<Input<KeyCode>>,
+    selected_q: Query<&UnitId, SelectedUnit>,
+    other_q: Query<(&UnitId, &BoardLoc, Option<&Selectable>), OtherUnit>,
+    target_q: Query<&MoveTarget, With<BaseHex>>,
+    mut ev_command_submitted: EventWriter<CommandSubmitted>,
 ) {
-    for ev in ev_double_clicked.iter() {
-        if let Ok((unit_entity, board_loc)) = unit_q.get_single() {
-            if let Some(move_target) = tile_q
-                .iter()
-                .find(|&x| x.0 == ev.0 && ev.0 != board_loc.hex)
-            {
-                move_target_ev.send(MoveTargetConfirmed {
-                    unit: unit_entity,
-                    from: board_loc.hex,
-                    to: move_target.0,
-                });
-            }
+    if !keyboard_input.just_pressed(KeyCode::Return) {
+        return;
+    }
+    let (Ok(unit_id), Ok(target)) = (selected_q.get_single(), target_q.get_single()) else {
+        return;
+    };
+    let occupant = other_q.iter().find(|(_, loc, _)| loc.hex == target.0);
+    ev_command_submitted.send(CommandSubmitted(order(*unit_id, target.0, occupant)));
+}
+
+// Takes back the order being shown, or the selection if there isn't one.
+fn cancel_on_right_click(
+    mut commands: Commands,
+    selected_q: Query<Entity, SelectedUnit>,
+    tile_q: Query<Entity, ShownOrder>,
+) {
+    if tile_q.is_empty() {
+        for unit in selected_q.iter() {
+            commands.entity(unit).remove::<Selected>();
         }
+        return;
+    }
+    for tile in tile_q.iter() {
+        commands
+            .entity(tile)
+            .remove::<MoveTarget>()
+            .remove::<PathPreview>()
+            .remove::<ZoneOfControlStop>();
     }
 }
 
 fn on_move_target_confirmed(
     mut commands: Commands,
     mut move_target_ev: EventReader<MoveTargetConfirmed>,
-    mut unit_q: Query<(Entity, &mut Transform, &mut BoardLoc), (With<Unit>, With<Selected>)>,
+    unit_q: Query<(Entity, &Transform), With<Unit>>,
+    zone_of_control_q: ZoneOfControlQuery,
+    speed: Res<AnimationSpeed>,
+    hex_map: Res<HexMap>,
+    board: Board,
 ) {
     for ev in move_target_ev.iter() {
-        if let Some((unit_entity, _unit_transform, _board_loc)) =
-            unit_q.iter_mut().find(|(entity, _, _)| *entity == ev.unit)
-        {
-            if let Some(path) = hexx::algorithms::a_star(ev.from, ev.to, |_| Some(0)) {
-                let hexes: Vec<Hex> = path.iter().take(2).map(|x| *x).collect();
-                let direction = hexes
-                    .get(0)
-                    .unwrap()
-                    .neighbor_direction(*hexes.get(1).unwrap())
-                    .unwrap();
+        if let Ok((unit_entity, transform)) = unit_q.get(ev.unit) {
+            let zone_of_control = zone_of_control(unit_entity, &zone_of_control_q);
+            if let Some(path) =
+                find_path(ev.from, ev.to, &hex_map, &board.elevation, &zone_of_control)
+            {
+                let mut hexes = path.into_iter().skip(1);
+                let Some(towards) = hexes.next() else {
+                    continue;
+                };
+                let remaining = hexes.collect::<Vec<Hex>>();
+                let direction = ev.from.neighbor_direction(towards).unwrap();
                 commands
                     .entity(unit_entity)
-                    .insert(Path(path.iter().skip(1).map(|x| *x).collect::<Vec<Hex>>()))
-                    .insert(Moving {
-                        direction,
-                        towards: path.into_iter().take(2).into_iter().last().unwrap(),
-                    });
+                    .insert(MoveTween::new(
+                        transform.translation.xy(),
+                        board.world_pos(towards),
+                        Easing::for_segment(true, remaining.is_empty()),
+                        *speed,
+                    ))
+                    .insert(Path(remaining))
+                    .insert(Moving { direction, towards });
             }
         }
     }
 }
 
-fn flip_sprite_when_moving(mut q: Query<(&mut Sprite, &Moving), Changed<Moving>>) {
-    for (mut sprite, moving) in q.iter_mut() {
-        match moving.direction {
-            hexx::Direction::TopRight => sprite.flip_x = false,
-            hexx::Direction::Top => (),
-            hexx::Direction::TopLeft => sprite.flip_x = true,
-            hexx::Direction::BottomLeft => sprite.flip_x = true,
-            hexx::Direction::Bottom => (),
-            hexx::Direction::BottomRight => sprite.flip_x = false,
-        }
-    }
-}
-
 fn move_along_path(
     mut commands: Commands,
-    mut transform_q: Query<(
+    mut unit_q: Query<(
         Entity,
         &mut Transform,
         &mut Moving,
         &mut Path,
         &mut BoardLoc,
+        &mut MoveTween,
     )>,
     time: Res<Time>,
+    speed: Res<AnimationSpeed>,
+    board: Board,
+    mut ev_unit_arrived: EventWriter<UnitArrived>,
 ) {
-    for (entity, mut transform, mut moving, mut path, mut board_loc) in transform_q.iter_mut() {
-        let Vec2 { x, y } = LAYOUT.hex_to_world_pos(moving.towards);
-
-        transform.translation = transform
-            .translation
-            .lerp(Vec3 { x, y, z: 10.0 }, 5.0 * time.delta_seconds());
-        if transform.translation.xy().round() == Vec2::new(x, y).round() {
-            if let Some(hex) = path.0.clone().iter().next() {
-                let next_direction = board_loc.hex.neighbor_direction(*hex).unwrap();
-                board_loc.set_if_neq(BoardLoc { hex: *hex });
-                moving.set_if_neq(Moving {
-                    towards: *hex,
-                    direction: next_direction,
-                });
-                path.set_if_neq(Path(
-                    path.0.clone().into_iter().skip(1).collect::<Vec<Hex>>(),
-                ));
-            } else {
-                *transform = Transform::from_xyz(x, y, 10.0);
-                commands.entity(entity).remove::<Path>().remove::<Moving>();
+    for (entity, mut transform, mut moving, mut path, mut board_loc, mut tween) in unit_q.iter_mut()
+    {
+        tween.elapsed += time.delta_seconds();
+        // Leftover time carries over into the next hex, so a slow frame (or instant
+        // speed) can cover several hexes without the unit's speed changing.
+        while tween.is_finished() {
+            let arrived_at = moving.towards;
+            board_loc.set_if_neq(BoardLoc { hex: arrived_at });
+            ev_unit_arrived.send(UnitArrived {
+                unit: entity,
+                hex: arrived_at,
+                end_of_path: path.0.is_empty(),
+            });
+            if path.0.is_empty() {
+                break;
             }
+
+            let next = path.0.remove(0);
+            moving.set_if_neq(Moving {
+                towards: next,
+                direction: arrived_at.neighbor_direction(next).unwrap(),
+            });
+            let leftover = tween.elapsed - tween.duration;
+            *tween = MoveTween::new(
+                tween.end,
+                board.world_pos(next),
+                Easing::for_segment(false, path.0.is_empty()),
+                *speed,
+            );
+            tween.elapsed = leftover;
+        }
+
+        let z = transform.translation.z;
+        transform.translation = tween.position().extend(z);
+        if tween.is_finished() {
+            commands
+                .entity(entity)
+                .remove::<Path>()
+                .remove::<Moving>()
+                .remove::<MoveTween>();
         }
     }
 }
 
-fn did_not_click_selected_unit(
-    mut ev_new_tile_clicked: EventReader<NewTileClicked>,
-    unit_q: Query<&BoardLoc, (With<Selected>, With<Unit>)>,
-) -> bool {
-    for ev in ev_new_tile_clicked.iter() {
-        return unit_q
-            .iter()
-            .find(|&board_loc| board_loc.hex != ev.0)
-            .is_some();
+fn cycle_animation_speed(keyboard_input: Res<Input<KeyCode>>, mut speed: ResMut<AnimationSpeed>) {
+    if keyboard_input.just_pressed(KeyCode::F) {
+        *speed = speed.next();
     }
-    false
 }
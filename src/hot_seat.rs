@@ -0,0 +1,489 @@
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use hexx::Hex;
+use serde::Deserialize;
+
+use crate::{
+    abilities::AbilityDefs,
+    components::{Faction, Moving, Recruited, Selectable, Selected, Unit, UnitId},
+    events::{CommandSubmitted, NewMatch, TurnPassed},
+    game_command::{BoardState, GameCommand, PendingChanges},
+    helpers::data::load_ron,
+    recruitment::plan_recruits,
+    replay::ReplayPlayback,
+    resources::TurnQueue,
+    rng::GameRng,
+    rules::MatchState,
+    startup::MatchSetup,
+    states::{AppState, PlayerState},
+};
+
+// Who plays each faction is set in `assets/players.ron`.
+const PLAYERS_FILE: &str = "players.ron";
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Controller {
+    Human,
+    #[default]
+    Ai,
+    // Played on another machine, and sent over the network.
+    Remote,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PlayerConfig {
+    pub faction: u32,
+    pub name: String,
+    pub controller: Controller,
+}
+
+// Factions that aren't listed are played by the AI.
+#[derive(Resource, Deserialize, Default, Clone, Debug)]
+pub struct Players {
+    pub players: Vec<PlayerConfig>,
+}
+
+impl Players {
+    fn get(&self, faction: Faction) -> Option<&PlayerConfig> {
+        self.players
+            .iter()
+            .find(|player| player.faction == faction.0)
+    }
+
+    pub fn is_human(&self, faction: Faction) -> bool {
+        self.get(faction)
+            .is_some_and(|player| player.controller == Controller::Human)
+    }
+
+    pub fn humans(&self) -> impl Iterator<Item = Faction> + '_ {
+        self.players
+            .iter()
+            .filter(|player| player.controller == Controller::Human)
+            .map(|player| Faction(player.faction))
+    }
+
+    // The faction whose side of the match is shown: the human player whose turn
+    // it is, or else the first human player at this machine.
+    pub fn viewing(&self, active: Faction) -> Faction {
+        if self.is_human(active) {
+            return active;
+        }
+        self.humans().next().unwrap_or(active)
+    }
+
+    pub fn controller(&self, faction: Faction) -> Controller {
+        self.get(faction)
+            .map(|player| player.controller)
+            .unwrap_or_default()
+    }
+
+    pub fn set_controller(&mut self, faction: Faction, controller: Controller) {
+        match self.players.iter_mut().find(|x| x.faction == faction.0) {
+            Some(player) => player.controller = controller,
+            None => self.players.push(PlayerConfig {
+                faction: faction.0,
+                name: format!("Faction {}", faction.0),
+                controller,
+            }),
+        }
+    }
+
+    pub fn name(&self, faction: Faction) -> String {
+        self.get(faction)
+            .map(|player| player.name.clone())
+            .unwrap_or_else(|| format!("Faction {}", faction.0))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CameraView {
+    translation: Vec3,
+    scale: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct HotSeat {
+    // The human player who last had the board. The board is hidden whenever it
+    // passes to a different one.
+    pub last_human: Option<Faction>,
+    // Where each human player left the camera.
+    cameras: HashMap<Faction, CameraView>,
+}
+
+// Covers the board while the device is passed to the next player.
+#[derive(Component)]
+pub struct HandOverScreen;
+
+pub struct HotSeatPlugin;
+
+impl Plugin for HotSeatPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_ron::<Players>(PLAYERS_FILE).unwrap_or_default())
+            .init_resource::<HotSeat>()
+            .add_systems(
+                Update,
+                (
+                    reset_hot_seat.run_if(on_event::<NewMatch>()),
+                    update_selectable,
+                    hand_over,
+                )
+                    .chain()
+                    .after(MatchSetup),
+            )
+            .add_systems(
+                Update,
+                pass_ai_turns
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(not(resource_exists::<ReplayPlayback>())),
+            )
+            .add_systems(OnEnter(PlayerState::PassingDevice), spawn_hand_over_screen)
+            .add_systems(
+                Update,
+                continue_after_hand_over.run_if(in_state(PlayerState::PassingDevice)),
+            )
+            .add_systems(OnExit(PlayerState::PassingDevice), take_over_board);
+    }
+}
+
+fn reset_hot_seat(
+    turn_queue: Res<TurnQueue>,
+    players: Res<Players>,
+    mut hot_seat: ResMut<HotSeat>,
+) {
+    let active = turn_queue.active_faction();
+    *hot_seat = HotSeat {
+        last_human: players.is_human(active).then_some(active),
+        ..default()
+    };
+}
+
+type SelectableState = (
+    Entity,
+    &'static Faction,
+    Option<&'static Recruited>,
+    Option<&'static Selectable>,
+);
+
+// Only the units of the human player whose turn it is can be selected, and not
+// ones recruited this turn.
+fn update_selectable(
+    mut commands: Commands,
+    turn_queue: Res<TurnQueue>,
+    players: Res<Players>,
+    unit_q: Query<SelectableState, With<Unit>>,
+) {
+    let active = turn_queue.active_faction();
+    let human = players.is_human(active);
+    for (entity, faction, recruited, selectable) in unit_q.iter() {
+        let fresh = recruited.is_some_and(|recruited| recruited.0 == turn_queue.turn_number);
+        match (human && *faction == active && !fresh, selectable.is_some()) {
+            (true, false) => {
+                commands.entity(entity).insert(Selectable);
+            }
+            (false, true) => {
+                commands
+                    .entity(entity)
+                    .remove::<Selectable>()
+                    .remove::<Selected>();
+            }
+            _ => (),
+        }
+    }
+}
+
+fn hand_over(
+    mut ev_turn_passed: EventReader<TurnPassed>,
+    players: Res<Players>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut hot_seat: ResMut<HotSeat>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<Camera2d>>,
+    mut next_state: ResMut<NextState<PlayerState>>,
+) {
+    for ev in ev_turn_passed.iter() {
+        if players.is_human(ev.from) {
+            if let Ok((transform, projection)) = camera_q.get_single() {
+                hot_seat.cameras.insert(
+                    ev.from,
+                    CameraView {
+                        translation: transform.translation,
+                        scale: projection.scale,
+                    },
+                );
+            }
+        }
+        if !players.is_human(ev.to) {
+            continue;
+        }
+        // Nobody needs to look away while a replay plays.
+        if hot_seat.last_human.is_some_and(|last| last != ev.to) && playback.is_none() {
+            next_state.set(PlayerState::PassingDevice);
+        }
+        hot_seat.last_human = Some(ev.to);
+    }
+}
+
+// What the AI plans its turn from.
+#[derive(SystemParam)]
+struct AiBoard<'w, 's> {
+    board_state: BoardState<'w, 's>,
+    pending_changes: Res<'w, PendingChanges>,
+    ability_defs: Res<'w, AbilityDefs>,
+    rng: ResMut<'w, GameRng>,
+}
+
+// The turns that were last given orders for and passed, so each is only done
+// once while the commands go through.
+#[derive(Default)]
+struct AiProgress {
+    ordered: Option<(i32, usize)>,
+    passed: Option<(i32, usize)>,
+}
+
+// Orders for each of `faction`'s units, going by the stats its status effects
+// leave it: attack whatever it can hit, or else get as close as it can to the
+// nearest enemy and attack from there. Plays them on `state` as it goes.
+fn plan_orders(
+    state: &mut MatchState,
+    faction: Faction,
+    ability_defs: &AbilityDefs,
+) -> Vec<GameCommand> {
+    let ids: Vec<UnitId> = state
+        .units
+        .iter()
+        .filter(|unit| unit.faction == faction && unit.stats().can_act)
+        .map(|unit| unit.id)
+        .collect();
+    let mut commands = Vec::new();
+    for id in ids {
+        if let Some(attack) = plan_attack(state, faction, id, ability_defs) {
+            state.apply(&attack, ability_defs);
+            commands.push(attack);
+            continue;
+        }
+        let Some(unit) = state.unit(id) else {
+            continue;
+        };
+        let enemies: Vec<Hex> = state
+            .units
+            .iter()
+            .filter(|other| other.faction != faction)
+            .map(|other| other.hex)
+            .collect();
+        let distance = |hex: Hex| {
+            enemies
+                .iter()
+                .map(|enemy| hex.unsigned_distance_to(*enemy))
+                .min()
+        };
+        let Some(to) = state
+            .movement_range(unit)
+            .into_iter()
+            .filter(|hex| state.occupant(*hex).is_none())
+            .min_by_key(|hex| (distance(*hex), hex.x, hex.y))
+            .filter(|hex| distance(*hex) < distance(unit.hex))
+        else {
+            continue;
+        };
+        let command = GameCommand::Move {
+            unit: id,
+            to: (to.x, to.y),
+        };
+        if state.validate(faction, &command, ability_defs).is_err() {
+            continue;
+        }
+        state.apply(&command, ability_defs);
+        commands.push(command);
+        if let Some(attack) = plan_attack(state, faction, id, ability_defs) {
+            state.apply(&attack, ability_defs);
+            commands.push(attack);
+        }
+    }
+    commands
+}
+
+// An attack `unit` can make right now, on the weakest enemy it can hit.
+fn plan_attack(
+    state: &MatchState,
+    faction: Faction,
+    unit: UnitId,
+    ability_defs: &AbilityDefs,
+) -> Option<GameCommand> {
+    let mut enemies: Vec<(i32, UnitId)> = state
+        .units
+        .iter()
+        .filter(|other| other.faction != faction)
+        .map(|other| (other.health, other.id))
+        .collect();
+    enemies.sort_by_key(|(health, id)| (*health, id.0));
+    enemies.into_iter().find_map(|(_, target)| {
+        state
+            .validate(faction, &GameCommand::Attack { unit, target }, ability_defs)
+            .ok()
+    })
+}
+
+// The AI spends its gold on recruits and gives its units their orders, then
+// passes its turn once they've been played out.
+fn pass_ai_turns(
+    turn_queue: Res<TurnQueue>,
+    players: Res<Players>,
+    mut ai_board: AiBoard,
+    moving_q: Query<(), With<Moving>>,
+    mut ev_new_match: EventReader<NewMatch>,
+    mut ev_command_submitted: EventWriter<CommandSubmitted>,
+    mut progress: Local<AiProgress>,
+) {
+    if !ev_new_match.is_empty() {
+        ev_new_match.clear();
+        *progress = AiProgress::default();
+    }
+    let faction = turn_queue.active_faction();
+    if players.controller(faction) != Controller::Ai
+        || !moving_q.is_empty()
+        || !ai_board.pending_changes.0.is_empty()
+    {
+        return;
+    }
+    let turn = (turn_queue.turn_number, turn_queue.active);
+    if progress.ordered != Some(turn) {
+        progress.ordered = Some(turn);
+        let mut state = ai_board.board_state.snapshot();
+        let mut commands = plan_recruits(
+            &mut state,
+            faction,
+            &ai_board.ability_defs,
+            &mut ai_board.rng.ai,
+        );
+        commands.extend(plan_orders(&mut state, faction, &ai_board.ability_defs));
+        for command in commands {
+            ev_command_submitted.send(CommandSubmitted(command));
+        }
+        // Pass on a later frame, once the orders have been played out.
+        return;
+    }
+    if progress.passed == Some(turn) {
+        return;
+    }
+    progress.passed = Some(turn);
+    ev_command_submitted.send(CommandSubmitted(GameCommand::EndTurn));
+}
+
+fn spawn_hand_over_screen(
+    mut commands: Commands,
+    turn_queue: Res<TurnQueue>,
+    players: Res<Players>,
+) {
+    commands
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                z_index: ZIndex::Global(100),
+                ..default()
+            },
+            HandOverScreen,
+            Name::new("Hand Over Screen"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    format!(
+                        "{}'s turn\nPass the device, then click or press Enter",
+                        players.name(turn_queue.active_faction())
+                    ),
+                    TextStyle {
+                        font_size: 32.0,
+                        ..default()
+                    },
+                )
+                .with_text_alignment(TextAlignment::Center),
+            );
+        });
+}
+
+fn continue_after_hand_over(
+    keyboard_input: Res<Input<KeyCode>>,
+    screen_q: Query<&Interaction, (Changed<Interaction>, With<HandOverScreen>)>,
+    mut next_state: ResMut<NextState<PlayerState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Return)
+        || screen_q
+            .iter()
+            .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        next_state.set(PlayerState::Idle);
+    }
+}
+
+fn take_over_board(
+    mut commands: Commands,
+    screen_q: Query<Entity, With<HandOverScreen>>,
+    turn_queue: Res<TurnQueue>,
+    hot_seat: Res<HotSeat>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+) {
+    for entity in screen_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(view) = hot_seat.cameras.get(&turn_queue.active_faction()) else {
+        return;
+    };
+    if let Ok((mut transform, mut projection)) = camera_q.get_single_mut() {
+        transform.translation = view.translation;
+        projection.scale = view.scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        archetypes::UnitArchetypes,
+        map::{MapData, MapUnit},
+        status_effects::{StatusEffect, StatusKind},
+        structures::StructureDefs,
+    };
+
+    #[test]
+    fn orders_go_by_effective_stats() {
+        let unit = |hex, faction| MapUnit {
+            archetype: String::from("Tidehunter"),
+            hex,
+            faction,
+        };
+        let map = MapData {
+            radius: Some(6),
+            units: vec![unit((-5, 0), 0), unit((0, -5), 0), unit((5, 0), 1)],
+            ..Default::default()
+        };
+        let ability_defs = AbilityDefs::load();
+        let mut state = MatchState::new(&map, &UnitArchetypes::load(), &StructureDefs::load());
+        let effect = |kind, potency| StatusEffect {
+            kind,
+            turns: 1,
+            potency,
+        };
+        state.units[0]
+            .status_effects
+            .add(effect(StatusKind::Slow, 2));
+        state.units[1]
+            .status_effects
+            .add(effect(StatusKind::Stun, 0));
+
+        let orders = plan_orders(&mut state, Faction(0), &ability_defs);
+        // The slowed unit only gets half as far, and the stunned one stays put.
+        assert_eq!(
+            orders,
+            vec![GameCommand::Move {
+                unit: UnitId(0),
+                to: (-3, 0),
+            }]
+        );
+    }
+}
@@ -0,0 +1,36 @@
+pub mod abilities;
+pub mod animation;
+pub mod archetypes;
+pub mod audio;
+pub mod bundles;
+pub mod combat;
+pub mod components;
+pub mod constants;
+pub mod context_menu;
+pub mod controls;
+pub mod depth;
+pub mod editor;
+pub mod events;
+pub mod facing;
+pub mod game_command;
+pub mod helpers;
+pub mod hot_seat;
+pub mod line_of_sight;
+pub mod map;
+pub mod network;
+pub mod objectives;
+pub mod player;
+pub mod recruitment;
+pub mod replay;
+pub mod resources;
+pub mod rng;
+pub mod rules;
+pub mod server;
+pub mod settings;
+pub mod startup;
+pub mod states;
+pub mod status_effects;
+pub mod structures;
+pub mod threat;
+pub mod tiles;
+pub mod ui;
@@ -0,0 +1,142 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use hexx::Hex;
+use serde::Deserialize;
+
+use crate::{
+    abilities::{AbilityDefs, ActiveAbility},
+    components::{BoardLoc, Unit},
+    constants::COVER_DEFENSE_BONUS,
+    helpers::board::Board,
+    resources::{CursorPos, Elevation, HexMap, TerrainMap},
+    states::PlayerState,
+    structures::Structures,
+};
+
+// What an ability has to see its target past.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum SightRule {
+    #[default]
+    Terrain,
+    // Units standing in the way block it as well, like they would a dart.
+    TerrainAndUnits,
+    // Lobbed, or magic, so it reaches targets out of sight.
+    Ignored,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sight {
+    Clear,
+    // In sight, but behind cover.
+    Partial,
+    Blocked,
+}
+
+impl Sight {
+    // Defense the target gains against whoever is looking at it.
+    pub fn cover(&self) -> i32 {
+        match self {
+            Sight::Partial => COVER_DEFENSE_BONUS,
+            _ => 0,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            Sight::Clear => Color::rgb(0.3, 1.0, 0.4),
+            Sight::Partial => Color::rgb(1.0, 0.85, 0.2),
+            Sight::Blocked => Color::rgb(1.0, 0.25, 0.2),
+        }
+    }
+}
+
+// The map as sight lines see it.
+#[derive(SystemParam)]
+pub struct SightLines<'w> {
+    pub hex_map: Res<'w, HexMap>,
+    pub terrain: Res<'w, TerrainMap>,
+    pub elevation: Res<'w, Elevation>,
+    // Towers and the like let ranged abilities reach further.
+    pub structures: Res<'w, Structures>,
+}
+
+// Traces a hex line from `from` to `to`, and checks the hexes in between.
+// Blocking terrain, hexes higher than both ends, and any of `units` block it,
+// and terrain that gives cover makes it partial.
+pub fn line_of_sight(
+    from: Hex,
+    to: Hex,
+    terrain: &TerrainMap,
+    elevation: &Elevation,
+    units: &[Hex],
+) -> Sight {
+    let height = elevation.level(from).max(elevation.level(to));
+    let mut sight = Sight::Clear;
+    for hex in from.line_to(to).filter(|hex| *hex != from && *hex != to) {
+        let ground = terrain.get(hex);
+        if ground.blocks_sight() || elevation.level(hex) > height || units.contains(&hex) {
+            return Sight::Blocked;
+        }
+        if ground.gives_cover() {
+            sight = Sight::Partial;
+        }
+    }
+    sight
+}
+
+pub struct LineOfSightPlugin;
+
+impl Plugin for LineOfSightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            draw_sight_preview.run_if(in_state(PlayerState::Targeting)),
+        );
+    }
+}
+
+// Draws a line from the caster to the hovered hex, colored by how well the
+// caster can see it.
+fn draw_sight_preview(
+    mut gizmos: Gizmos,
+    cursor_pos: Res<CursorPos>,
+    board: Board,
+    active_ability: Res<ActiveAbility>,
+    ability_defs: Res<AbilityDefs>,
+    sight_lines: SightLines,
+    unit_q: Query<&BoardLoc, With<Unit>>,
+) {
+    let Some(targeting) = &active_ability.0 else {
+        return;
+    };
+    let (Some(ability), Ok(caster_loc)) = (
+        ability_defs.0.get(&targeting.ability),
+        unit_q.get(targeting.caster),
+    ) else {
+        return;
+    };
+    let hovered = board.world_pos_to_hex(cursor_pos.0);
+    if hovered == caster_loc.hex
+        || !ability
+            .valid_targets(
+                caster_loc.hex,
+                &sight_lines.hex_map,
+                &sight_lines.structures,
+            )
+            .contains(&hovered)
+    {
+        return;
+    }
+    let units: Vec<Hex> = unit_q.iter().map(|board_loc| board_loc.hex).collect();
+    let sight = ability.sight_to(
+        caster_loc.hex,
+        hovered,
+        &sight_lines.terrain,
+        &board.elevation,
+        &units,
+    );
+    gizmos.line_2d(
+        board.world_pos(caster_loc.hex),
+        board.world_pos(hovered),
+        sight.color(),
+    );
+}
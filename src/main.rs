@@ -3,32 +3,42 @@ use bevy::{
     utils::Duration,
 };
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use components::{BoardLoc, HexTile, Layer, Unit};
-use controls::cursor::CursorPlugin;
-use events::EventsPlugin;
-use helpers::unit::UnitPlugin;
-use player::PlayerPlugin;
-use resources::*;
-use startup::StartupPlugin;
-use states::{AppState, PlayerState};
-use tiles::{layers::LayersPlugin, TilePlugin};
-use turn_queue::TurnQueuePlugin;
-use ui::GameUI;
-
-mod bundles;
-mod components;
-mod constants;
-mod controls;
-mod enums;
-mod events;
-mod helpers;
-mod player;
-mod resources;
-mod startup;
-mod states;
-mod tiles;
-mod turn_queue;
-mod ui;
+use bevy_toy_project::{
+    abilities::AbilitiesPlugin,
+    animation::UnitAnimationPlugin,
+    archetypes::ArchetypesPlugin,
+    audio::GameAudioPlugin,
+    combat::CombatPlugin,
+    components::{
+        ActionPoints, BoardLoc, EffectiveStats, Faction, HexTile, Layer, LayerId, MapUnit,
+        MovementSpent, Unit, UnitId,
+    },
+    context_menu::ContextMenuPlugin,
+    controls::cursor::CursorPlugin,
+    depth::DepthPlugin,
+    editor::EditorPlugin,
+    events::EventsPlugin,
+    facing::FacingPlugin,
+    game_command::GameCommandPlugin,
+    helpers::{camera, unit::UnitPlugin},
+    hot_seat::HotSeatPlugin,
+    line_of_sight::LineOfSightPlugin,
+    network::NetworkPlugin,
+    objectives::ObjectivesPlugin,
+    player::PlayerPlugin,
+    recruitment::RecruitmentPlugin,
+    replay::ReplayPlugin,
+    resources::*,
+    rng::RngPlugin,
+    settings::SettingsPlugin,
+    startup::StartupPlugin,
+    states::{AppState, PlayerState},
+    status_effects::StatusEffectsPlugin,
+    structures::StructuresPlugin,
+    threat::ThreatPlugin,
+    tiles::{layers::LayersPlugin, TilePlugin},
+    ui::GameUI,
+};
 
 fn main() {
     App::new()
@@ -53,21 +63,50 @@ fn main() {
         .init_resource::<CursorPos>()
         .init_resource::<TurnQueue>()
         .init_resource::<HexMap>()
+        .init_resource::<AnimationSpeed>()
         .add_state::<AppState>()
         .add_state::<PlayerState>()
         .register_type::<Unit>()
+        .register_type::<UnitId>()
         .register_type::<BoardLoc>()
+        .register_type::<Faction>()
+        .register_type::<ActionPoints>()
+        .register_type::<MovementSpent>()
+        .register_type::<EffectiveStats>()
+        .register_type::<MapUnit>()
         .register_type::<HexTile>()
         .register_type::<Layer>()
+        .register_type::<LayerId>()
+        .register_type::<AnimationSpeed>()
         .add_plugins(EventsPlugin)
+        .add_plugins(SettingsPlugin)
+        .add_plugins(ArchetypesPlugin)
         .add_plugins(StartupPlugin)
         .add_plugins(GameUI)
-        .add_plugins(TurnQueuePlugin)
         .add_plugins(CursorPlugin)
         .add_plugins(TilePlugin)
         .add_plugins(UnitPlugin)
+        .add_plugins(UnitAnimationPlugin)
+        .add_plugins(FacingPlugin)
+        .add_plugins(GameAudioPlugin)
         .add_plugins(PlayerPlugin)
         .add_plugins(LayersPlugin)
-        .add_systems(Update, helpers::camera::movement)
+        .add_plugins(ThreatPlugin)
+        .add_plugins(ObjectivesPlugin)
+        .add_plugins(AbilitiesPlugin)
+        .add_plugins(LineOfSightPlugin)
+        .add_plugins(ContextMenuPlugin)
+        .add_plugins(CombatPlugin)
+        .add_plugins(StatusEffectsPlugin)
+        .add_plugins(StructuresPlugin)
+        .add_plugins(RecruitmentPlugin)
+        .add_plugins(DepthPlugin)
+        .add_plugins(EditorPlugin)
+        .add_plugins(GameCommandPlugin)
+        .add_plugins(ReplayPlugin)
+        .add_plugins(RngPlugin)
+        .add_plugins(HotSeatPlugin)
+        .add_plugins(NetworkPlugin)
+        .add_systems(Update, camera::movement)
         .run();
 }
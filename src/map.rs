@@ -0,0 +1,180 @@
+use std::{collections::BTreeMap, path::Path};
+
+use bevy::prelude::*;
+use hexx::{shapes, Hex};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::{BASE_TILE_TEXTURE, CENTER_HEX},
+    helpers::data::{load_ron, save_ron},
+    objectives::ObjectiveKind,
+};
+
+// Maps are authored in `assets/maps/*.ron`. Hexes are written as `(x, y)` axial
+// coordinates.
+pub const DEFAULT_MAP: &str = "maps/skirmish.ron";
+// Maps saved from the editor.
+pub const USER_MAPS_DIR: &str = "maps/user";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Orientation {
+    #[default]
+    Flat,
+    Pointy,
+}
+
+// How the map's hexes are laid out in the world, and the tile art drawn for
+// them.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LayoutConfig {
+    pub orientation: Orientation,
+    pub hex_size: (f32, f32),
+    pub origin: (f32, f32),
+    pub tile_texture: String,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        LayoutConfig {
+            orientation: Orientation::Flat,
+            hex_size: (32.0, 18.0),
+            origin: (0.0, 0.0),
+            tile_texture: String::from(BASE_TILE_TEXTURE),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Reflect, Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Terrain {
+    #[default]
+    Grass,
+    Sand,
+    Forest,
+    Stone,
+    Water,
+}
+
+impl Terrain {
+    pub const ALL: [Terrain; 5] = [
+        Terrain::Grass,
+        Terrain::Sand,
+        Terrain::Forest,
+        Terrain::Stone,
+        Terrain::Water,
+    ];
+
+    // Tint applied to the base tile art.
+    pub fn color(&self) -> Color {
+        match self {
+            Terrain::Grass => Color::WHITE,
+            Terrain::Sand => Color::rgb(1.0, 0.9, 0.6),
+            Terrain::Forest => Color::rgb(0.45, 0.7, 0.4),
+            Terrain::Stone => Color::rgb(0.65, 0.65, 0.7),
+            Terrain::Water => Color::rgb(0.4, 0.6, 1.0),
+        }
+    }
+
+    // Nothing can be seen through it.
+    pub fn blocks_sight(&self) -> bool {
+        *self == Terrain::Stone
+    }
+
+    // Can be seen through, but gives cover to whatever is behind it.
+    pub fn gives_cover(&self) -> bool {
+        *self == Terrain::Forest
+    }
+}
+
+// Where a faction's units enter the map.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SpawnPoint {
+    pub hex: (i32, i32),
+    pub faction: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MapUnit {
+    pub archetype: String,
+    pub hex: (i32, i32),
+    pub faction: u32,
+}
+
+// A building on the map. `kind` names one of the structures in
+// `assets/structures`, and `owner` is the faction holding it at the start, if
+// any.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MapStructure {
+    pub kind: String,
+    pub hex: (i32, i32),
+    #[serde(default)]
+    pub owner: Option<u32>,
+}
+
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Debug)]
+pub struct MapData {
+    // Where the map was loaded from, or last saved to by the editor.
+    #[serde(skip)]
+    pub path: String,
+    pub name: String,
+    #[serde(default)]
+    pub layout: LayoutConfig,
+    // Fills a hexagon of this radius around the center, on top of `hexes`.
+    #[serde(default)]
+    pub radius: Option<u32>,
+    #[serde(default)]
+    pub hexes: Vec<(i32, i32)>,
+    // Height of each hex, in levels. Hexes that aren't listed are at level 0.
+    #[serde(default)]
+    pub elevation: BTreeMap<(i32, i32), u32>,
+    // Hexes that aren't listed are grass.
+    #[serde(default)]
+    pub terrain: BTreeMap<(i32, i32), Terrain>,
+    #[serde(default)]
+    pub units: Vec<MapUnit>,
+    #[serde(default)]
+    pub structures: Vec<MapStructure>,
+    // Gold every faction starts the match with.
+    #[serde(default)]
+    pub starting_gold: u32,
+    #[serde(default)]
+    pub spawn_points: Vec<SpawnPoint>,
+    #[serde(default)]
+    pub objectives: Vec<ObjectiveKind>,
+}
+
+impl MapData {
+    pub fn load(path: &str) -> MapData {
+        MapData {
+            path: String::from(path),
+            ..load_ron(path).unwrap_or_default()
+        }
+    }
+
+    // Saves the map among the user's maps, under the name of the file it came
+    // from, so the maps that ship with the game are never overwritten. Returns
+    // where it was saved, and from then on the map is the saved one.
+    pub fn save_as_user_map(&mut self) -> Option<String> {
+        let name = Path::new(&self.path).file_name()?.to_string_lossy();
+        let path = format!("{}/{}", USER_MAPS_DIR, name);
+        if !save_ron(&path, self) {
+            return None;
+        }
+        self.path = path.clone();
+        Some(path)
+    }
+
+    pub fn all_hexes(&self) -> Vec<Hex> {
+        let mut hexes: Vec<Hex> = self
+            .radius
+            .map(|radius| shapes::hexagon(CENTER_HEX, radius).collect())
+            .unwrap_or_default();
+        for &(x, y) in self.hexes.iter() {
+            let hex = Hex::new(x, y);
+            if !hexes.contains(&hex) {
+                hexes.push(hex);
+            }
+        }
+        hexes
+    }
+}
@@ -0,0 +1,787 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{BoardLoc, Facing, Faction, Moving, Unit, UnitId},
+    events::{CommandReceived, CommandRejected, CommandSubmitted, NewMatch, TurnStarted},
+    facing::direction_index,
+    game_command::{GameCommand, PendingChanges},
+    hot_seat::{Controller, Players},
+    map::MapData,
+    resources::TurnQueue,
+    rules::{Rejection, StateChange},
+    startup::new_seed,
+    states::{AppState, PlayerState},
+};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7777";
+
+const LOBBY_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+
+// Everything sent between the two games, or between a game and a dedicated
+// server. Each message is written as one line of RON.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum NetMessage {
+    // Sent by the host once someone joins: the match to play, and the faction
+    // the joining player plays.
+    Start {
+        seed: u64,
+        map: Box<MapData>,
+        faction: u32,
+    },
+    // Sent for every command submitted, with the faction it was given for.
+    Command {
+        faction: u32,
+        command: GameCommand,
+    },
+    // Sent by both sides at the start of every turn, once units have stopped.
+    Checksum {
+        turn: i32,
+        checksum: u64,
+    },
+    // Sent by a dedicated server to everyone when a command goes through.
+    Changes(Vec<StateChange>),
+    // Sent by a dedicated server to whoever sent a command it turned down.
+    Rejected {
+        command: GameCommand,
+        reason: Rejection,
+    },
+}
+
+pub enum NetEvent {
+    Connected(Sender<NetMessage>),
+    Received(NetMessage),
+    Disconnected(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetRole {
+    Host,
+    Client,
+    // Playing on a dedicated server, which runs the match. The board only
+    // shows the changes it sends back.
+    ServerClient,
+}
+
+// A network match, from the moment a game is hosted or joined. Between two
+// games, both run the whole simulation, and only exchange the commands their
+// players submit.
+#[derive(Resource)]
+pub struct NetSession {
+    pub role: NetRole,
+    pub status: String,
+    events: Mutex<Receiver<NetEvent>>,
+    outgoing: Option<Sender<NetMessage>>,
+    // Commands from the other player, submitted one at a time once units have
+    // stopped, the same way they were on the other side.
+    queue: VecDeque<(Faction, GameCommand)>,
+    // Turn whose checksum is due once units stop moving.
+    pending_checksum: Option<i32>,
+    local_checksums: BTreeMap<i32, u64>,
+    remote_checksums: BTreeMap<i32, u64>,
+    // The first turn the two games disagreed on.
+    pub desync: Option<i32>,
+}
+
+impl NetSession {
+    fn new(role: NetRole, status: String, events: Receiver<NetEvent>) -> Self {
+        NetSession {
+            role,
+            status,
+            events: Mutex::new(events),
+            outgoing: None,
+            queue: VecDeque::new(),
+            pending_checksum: None,
+            local_checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            desync: None,
+        }
+    }
+
+    fn send(&mut self, message: NetMessage) {
+        if let Some(outgoing) = &self.outgoing {
+            if outgoing.send(message).is_err() {
+                self.outgoing = None;
+            }
+        }
+    }
+
+    fn compare_checksums(&mut self, turn: i32) {
+        if let (Some(local), Some(remote)) = (
+            self.local_checksums.get(&turn),
+            self.remote_checksums.get(&turn),
+        ) {
+            if local != remote && self.desync.is_none() {
+                error!("Desync on turn {}", turn);
+                self.desync = Some(turn);
+            }
+        }
+    }
+}
+
+// Reads messages off the connection until it closes, and writes whatever is
+// sent to the channel handed over in `NetEvent::Connected`.
+pub fn start_connection(stream: TcpStream, events: Sender<NetEvent>) {
+    let Ok(read_stream) = stream.try_clone() else {
+        let _ = events.send(NetEvent::Disconnected(String::from(
+            "Could not open connection",
+        )));
+        return;
+    };
+    let (outgoing, outgoing_rx) = channel::<NetMessage>();
+    let _ = events.send(NetEvent::Connected(outgoing));
+
+    thread::spawn(move || {
+        let mut stream = stream;
+        for message in outgoing_rx.iter() {
+            let Ok(line) = ron::to_string(&message) else {
+                continue;
+            };
+            if writeln!(stream, "{}", line).is_err() {
+                break;
+            }
+        }
+    });
+    thread::spawn(move || {
+        for line in BufReader::new(read_stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            match ron::from_str(&line) {
+                Ok(message) => {
+                    if events.send(NetEvent::Received(message)).is_err() {
+                        return;
+                    }
+                }
+                Err(err) => error!("Could not parse network message: {}", err),
+            }
+        }
+        let _ = events.send(NetEvent::Disconnected(String::from(
+            "The other player left",
+        )));
+    });
+}
+
+fn host(address: &str) -> NetSession {
+    let (events, events_rx) = channel();
+    let status = match TcpListener::bind(address) {
+        Ok(listener) => {
+            thread::spawn(move || match listener.accept() {
+                Ok((stream, _)) => start_connection(stream, events),
+                Err(err) => {
+                    let _ = events.send(NetEvent::Disconnected(err.to_string()));
+                }
+            });
+            format!("Waiting for a player on {}", address)
+        }
+        Err(err) => format!("Could not host on {}: {}", address, err),
+    };
+    NetSession::new(NetRole::Host, status, events_rx)
+}
+
+fn join(address: &str, role: NetRole) -> NetSession {
+    let (events, events_rx) = channel();
+    let target = address.to_string();
+    thread::spawn(move || match TcpStream::connect(&target) {
+        Ok(stream) => start_connection(stream, events),
+        Err(err) => {
+            let _ = events.send(NetEvent::Disconnected(format!(
+                "Could not join {}: {}",
+                target, err
+            )));
+        }
+    });
+    NetSession::new(role, format!("Joining {}", address), events_rx)
+}
+
+// Whether a dedicated server runs the rules of the match, rather than this
+// game.
+pub fn server_runs_rules(session: Option<Res<NetSession>>) -> bool {
+    session.is_some_and(|session| session.role == NetRole::ServerClient)
+}
+
+// Positions, health and facing of every unit, hashed (FNV-1a) in unit id
+// order.
+pub fn board_checksum(units: &mut [(UnitId, BoardLoc, i32, Facing)]) -> u64 {
+    units.sort_by_key(|(id, ..)| id.0);
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (id, board_loc, health, facing) in units.iter() {
+        for value in [
+            id.0 as i32,
+            board_loc.hex.x,
+            board_loc.hex.y,
+            *health,
+            direction_index(facing.0) as i32,
+        ] {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+    hash
+}
+
+// Address typed into the lobby.
+#[derive(Resource)]
+pub struct LobbyAddress(pub String);
+
+impl Default for LobbyAddress {
+    fn default() -> Self {
+        LobbyAddress(String::from(DEFAULT_ADDRESS))
+    }
+}
+
+#[derive(Component)]
+pub struct LobbyPanel;
+
+#[derive(Component)]
+pub struct LobbyText;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum LobbyButton {
+    Host,
+    Join,
+    JoinServer,
+}
+
+#[derive(Component)]
+pub struct NetStatusText;
+
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LobbyAddress>()
+            .add_systems(Update, toggle_lobby)
+            .add_systems(OnEnter(AppState::Lobby), spawn_lobby)
+            .add_systems(OnExit(AppState::Lobby), despawn_lobby)
+            .add_systems(
+                Update,
+                (edit_address, press_lobby_button, update_lobby_text)
+                    .run_if(in_state(AppState::Lobby)),
+            )
+            .add_systems(
+                Update,
+                spawn_net_status.run_if(resource_added::<NetSession>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    poll_connection,
+                    exchange_commands.run_if(in_state(AppState::InGame)),
+                    exchange_checksums,
+                    update_net_status,
+                )
+                    .chain()
+                    .run_if(resource_exists::<NetSession>()),
+            );
+    }
+}
+
+fn toggle_lobby(
+    keyboard_input: Res<Input<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F9) {
+        return;
+    }
+    match state.get() {
+        AppState::InGame | AppState::GameOver => next_state.set(AppState::Lobby),
+        AppState::Lobby => next_state.set(AppState::InGame),
+        AppState::LoadingMap | AppState::Editor => (),
+    }
+}
+
+fn spawn_lobby(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(10.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+                z_index: ZIndex::Global(50),
+                ..default()
+            },
+            LobbyPanel,
+            Name::new("Lobby"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 24.0,
+                        ..default()
+                    },
+                )
+                .with_text_alignment(TextAlignment::Center),
+                LobbyText,
+            ));
+            for (button, label) in [
+                (LobbyButton::Host, "Host"),
+                (LobbyButton::Join, "Join"),
+                (LobbyButton::JoinServer, "Join server"),
+            ] {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(150.0),
+                                padding: UiRect::all(Val::Px(8.0)),
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            background_color: LOBBY_BUTTON.into(),
+                            ..default()
+                        },
+                        button,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font_size: 24.0,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn despawn_lobby(mut commands: Commands, panel_q: Query<Entity, With<LobbyPanel>>) {
+    for entity in panel_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn edit_address(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut ev_received_character: EventReader<ReceivedCharacter>,
+    mut address: ResMut<LobbyAddress>,
+) {
+    for ev in ev_received_character.iter() {
+        if ev.char.is_ascii_alphanumeric() || ev.char == '.' || ev.char == ':' {
+            address.0.push(ev.char);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        address.0.pop();
+    }
+}
+
+fn press_lobby_button(
+    mut commands: Commands,
+    button_q: Query<(&Interaction, &LobbyButton), Changed<Interaction>>,
+    address: Res<LobbyAddress>,
+) {
+    for (interaction, button) in button_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // Replacing the session drops any connection it had.
+        commands.insert_resource(match button {
+            LobbyButton::Host => host(&address.0),
+            LobbyButton::Join => join(&address.0, NetRole::Client),
+            LobbyButton::JoinServer => join(&address.0, NetRole::ServerClient),
+        });
+    }
+}
+
+fn update_lobby_text(
+    address: Res<LobbyAddress>,
+    session: Option<Res<NetSession>>,
+    mut text_q: Query<&mut Text, With<LobbyText>>,
+) {
+    let status = session
+        .as_ref()
+        .map(|session| session.status.clone())
+        .unwrap_or_default();
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = format!(
+            "Network match (F9 to go back)\nAddress: {}\n{}",
+            address.0, status
+        );
+    }
+}
+
+// Who plays what in a network match: each side plays its own faction, and
+// the host (or a dedicated server) also plays any AI factions for everyone.
+fn assign_factions(
+    players: &mut Players,
+    map: &MapData,
+    role: NetRole,
+    local: Faction,
+    remote: Faction,
+) {
+    let factions: BTreeSet<u32> = map.units.iter().map(|unit| unit.faction).collect();
+    for faction in factions.into_iter().map(Faction) {
+        let controller = if faction == local {
+            Controller::Human
+        } else if faction == remote || role != NetRole::Host {
+            Controller::Remote
+        } else {
+            Controller::Ai
+        };
+        players.set_controller(faction, controller);
+    }
+}
+
+// What starting a network match sets off.
+#[derive(SystemParam)]
+struct MatchStart<'w> {
+    new_match: EventWriter<'w, NewMatch>,
+    app_state: ResMut<'w, NextState<AppState>>,
+    player_state: ResMut<'w, NextState<PlayerState>>,
+}
+
+impl<'w> MatchStart<'w> {
+    fn start(&mut self, seed: u64) {
+        self.new_match.send(NewMatch { seed });
+        self.app_state.set(AppState::InGame);
+        self.player_state.set(PlayerState::Idle);
+    }
+}
+
+fn poll_connection(
+    mut session: ResMut<NetSession>,
+    mut map: ResMut<MapData>,
+    mut players: ResMut<Players>,
+    mut match_start: MatchStart,
+    mut ev_command_rejected: EventWriter<CommandRejected>,
+    mut pending_changes: ResMut<PendingChanges>,
+) {
+    let events: Vec<NetEvent> = session.events.lock().unwrap().try_iter().collect();
+    for event in events {
+        match event {
+            NetEvent::Connected(outgoing) => {
+                session.outgoing = Some(outgoing);
+                match session.role {
+                    NetRole::Host => (),
+                    NetRole::Client => {
+                        session.status = String::from("Connected, waiting for the host");
+                        continue;
+                    }
+                    NetRole::ServerClient => {
+                        session.status = String::from("Connected, waiting for other players");
+                        continue;
+                    }
+                }
+                let factions: BTreeSet<u32> = map.units.iter().map(|unit| unit.faction).collect();
+                let mut factions = factions.into_iter().map(Faction);
+                let (Some(local), Some(remote)) = (factions.next(), factions.next()) else {
+                    session.status = String::from("This map needs units of two factions");
+                    continue;
+                };
+                let seed = new_seed();
+                session.send(NetMessage::Start {
+                    seed,
+                    map: Box::new(map.clone()),
+                    faction: remote.0,
+                });
+                assign_factions(&mut players, &map, NetRole::Host, local, remote);
+                match_start.start(seed);
+                session.status = String::from("Playing as host");
+            }
+            NetEvent::Received(NetMessage::Start {
+                seed,
+                map: host_map,
+                faction,
+            }) => {
+                *map = MapData {
+                    path: map.path.clone(),
+                    ..*host_map
+                };
+                let local = Faction(faction);
+                let remote = map
+                    .units
+                    .iter()
+                    .map(|unit| Faction(unit.faction))
+                    .find(|x| *x != local)
+                    .unwrap_or_default();
+                let role = session.role;
+                assign_factions(&mut players, &map, role, local, remote);
+                match_start.start(seed);
+                session.status = match role {
+                    NetRole::ServerClient => format!("Playing faction {} on a server", faction),
+                    _ => String::from("Playing as client"),
+                };
+            }
+            NetEvent::Received(NetMessage::Command { faction, command }) => {
+                session.queue.push_back((Faction(faction), command));
+            }
+            NetEvent::Received(NetMessage::Changes(changes)) => {
+                pending_changes.0.push_back(changes);
+            }
+            NetEvent::Received(NetMessage::Rejected { command, reason }) => {
+                ev_command_rejected.send(CommandRejected { command, reason });
+            }
+            NetEvent::Received(NetMessage::Checksum { turn, checksum }) => {
+                session.remote_checksums.insert(turn, checksum);
+                session.compare_checksums(turn);
+            }
+            NetEvent::Disconnected(reason) => {
+                session.outgoing = None;
+                session.status = reason;
+                // Whatever the other player controlled is left to the AI.
+                for player in players.players.iter_mut() {
+                    if player.controller == Controller::Remote {
+                        player.controller = Controller::Ai;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Sends the commands submitted here to the other game, given for the faction
+// whose turn it is, and passes on the ones it sent.
+fn exchange_commands(
+    mut session: ResMut<NetSession>,
+    turn_queue: Res<TurnQueue>,
+    mut ev_command_submitted: EventReader<CommandSubmitted>,
+    mut ev_command_received: EventWriter<CommandReceived>,
+    moving_q: Query<(), With<Moving>>,
+) {
+    let faction = turn_queue.active_faction();
+    for ev in ev_command_submitted.iter() {
+        // Which unit is selected is nobody's business but this player's.
+        if session.role == NetRole::ServerClient && matches!(ev.0, GameCommand::SelectUnit(_)) {
+            continue;
+        }
+        session.send(NetMessage::Command {
+            faction: faction.0,
+            command: ev.0.clone(),
+        });
+    }
+    if !moving_q.is_empty() {
+        return;
+    }
+    if let Some((faction, command)) = session.queue.pop_front() {
+        ev_command_received.send(CommandReceived { faction, command });
+    }
+}
+
+fn exchange_checksums(
+    mut session: ResMut<NetSession>,
+    mut ev_turn_started: EventReader<TurnStarted>,
+    unit_q: Query<(&UnitId, &BoardLoc, &Unit, &Facing)>,
+    moving_q: Query<(), With<Moving>>,
+) {
+    // A dedicated server's changes are applied as they are, so there's
+    // nothing to compare.
+    if let Some(ev) = ev_turn_started.iter().last() {
+        if session.role != NetRole::ServerClient {
+            session.pending_checksum = Some(ev.turn_number);
+        }
+    }
+    let Some(turn) = session.pending_checksum else {
+        return;
+    };
+    // Units moving at the end of the turn may still be on their way here, but
+    // will have arrived on the other side.
+    if !moving_q.is_empty() {
+        return;
+    }
+    let mut units: Vec<(UnitId, BoardLoc, i32, Facing)> = unit_q
+        .iter()
+        .map(|(id, board_loc, unit, facing)| (*id, *board_loc, unit.health, *facing))
+        .collect();
+    let checksum = board_checksum(&mut units);
+    session.pending_checksum = None;
+    session.local_checksums.insert(turn, checksum);
+    session.send(NetMessage::Checksum { turn, checksum });
+    session.compare_checksums(turn);
+}
+
+fn spawn_net_status(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 18.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        }),
+        NetStatusText,
+        Name::new("Network Status"),
+    ));
+}
+
+fn update_net_status(session: Res<NetSession>, mut text_q: Query<&mut Text, With<NetStatusText>>) {
+    if !session.is_changed() {
+        return;
+    }
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = match session.desync {
+            Some(turn) => format!("{}\nDesynced on turn {}", session.status, turn),
+            None => session.status.clone(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bevy::ecs::event::ManualEventReader;
+    use hexx::Hex;
+
+    use super::*;
+
+    // Two games connected over a real socket, each running the systems that
+    // talk to the other.
+    fn connected_apps() -> (App, App) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (host_events, host_events_rx) = channel();
+        let accepting = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            start_connection(stream, host_events);
+        });
+        let (client_events, client_events_rx) = channel();
+        start_connection(TcpStream::connect(address).unwrap(), client_events);
+        accepting.join().unwrap();
+        // Both sides play as clients, so connecting doesn't start a match.
+        let app = |events| {
+            let mut app = App::new();
+            app.add_state::<AppState>()
+                .add_state::<PlayerState>()
+                .add_event::<NewMatch>()
+                .add_event::<CommandSubmitted>()
+                .add_event::<CommandReceived>()
+                .add_event::<CommandRejected>()
+                .add_event::<TurnStarted>()
+                .init_resource::<MapData>()
+                .init_resource::<Players>()
+                .init_resource::<TurnQueue>()
+                .init_resource::<PendingChanges>()
+                .insert_resource(NetSession::new(NetRole::Client, String::new(), events))
+                .add_systems(
+                    Update,
+                    (poll_connection, exchange_commands, exchange_checksums).chain(),
+                );
+            app
+        };
+        (app(host_events_rx), app(client_events_rx))
+    }
+
+    // Updates both games until `done` holds, or gives up after a while.
+    fn update_until(apps: &mut (App, App), mut done: impl FnMut(&mut App, &mut App) -> bool) {
+        let start = Instant::now();
+        while !done(&mut apps.0, &mut apps.1) {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            apps.0.update();
+            apps.1.update();
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn spawn_unit(app: &mut App, id: u32, hex: Hex, health: i32) {
+        app.world.spawn((
+            UnitId(id),
+            BoardLoc { hex },
+            Unit {
+                health,
+                max_health: 10,
+            },
+            Facing::default(),
+        ));
+    }
+
+    #[test]
+    fn commands_reach_the_other_game_with_their_faction() {
+        let mut apps = connected_apps();
+        let command = GameCommand::Move {
+            unit: UnitId(3),
+            to: (1, -1),
+        };
+        apps.1.world.send_event(CommandSubmitted(command.clone()));
+        let mut reader = ManualEventReader::<CommandReceived>::default();
+        let mut received = Vec::new();
+        update_until(&mut apps, |host, _| {
+            let events = host.world.resource::<Events<CommandReceived>>();
+            received.extend(
+                reader
+                    .iter(events)
+                    .map(|ev| (ev.faction, ev.command.clone())),
+            );
+            !received.is_empty()
+        });
+        let active = apps.1.world.resource::<TurnQueue>().active_faction();
+        assert_eq!(received, vec![(active, command)]);
+    }
+
+    #[test]
+    fn matching_boards_stay_in_sync() {
+        let mut apps = connected_apps();
+        for app in [&mut apps.0, &mut apps.1] {
+            spawn_unit(app, 0, Hex::ZERO, 10);
+            app.world.send_event(TurnStarted { turn_number: 1 });
+        }
+        update_until(&mut apps, |host, client| {
+            let compared = |app: &App| {
+                app.world
+                    .resource::<NetSession>()
+                    .remote_checksums
+                    .contains_key(&1)
+            };
+            compared(host) && compared(client)
+        });
+        assert_eq!(apps.0.world.resource::<NetSession>().desync, None);
+        assert_eq!(apps.1.world.resource::<NetSession>().desync, None);
+    }
+
+    #[test]
+    fn different_boards_are_a_desync() {
+        let mut apps = connected_apps();
+        spawn_unit(&mut apps.0, 0, Hex::ZERO, 10);
+        spawn_unit(&mut apps.1, 0, Hex::ZERO, 7);
+        for app in [&mut apps.0, &mut apps.1] {
+            app.world.send_event(TurnStarted { turn_number: 2 });
+        }
+        update_until(&mut apps, |host, client| {
+            let desync = |app: &App| app.world.resource::<NetSession>().desync;
+            desync(host).is_some() && desync(client).is_some()
+        });
+        assert_eq!(apps.0.world.resource::<NetSession>().desync, Some(2));
+        assert_eq!(apps.1.world.resource::<NetSession>().desync, Some(2));
+    }
+
+    #[test]
+    fn checksums_follow_the_board() {
+        let unit = |id, hex, health| (UnitId(id), BoardLoc { hex }, health, Facing::default());
+        let mut a = vec![unit(0, Hex::ZERO, 10), unit(1, Hex::new(1, 0), 5)];
+        let mut b = vec![unit(1, Hex::new(1, 0), 5), unit(0, Hex::ZERO, 10)];
+        assert_eq!(board_checksum(&mut a), board_checksum(&mut b));
+        let mut c = vec![unit(0, Hex::ZERO, 10), unit(1, Hex::new(0, 1), 5)];
+        assert_ne!(board_checksum(&mut a), board_checksum(&mut c));
+    }
+}
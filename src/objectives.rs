@@ -0,0 +1,335 @@
+use std::collections::BTreeSet;
+
+use bevy::{prelude::*, utils::HashMap};
+use hexx::Hex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{BoardLoc, Faction, MapUnit, Unit},
+    events::{DefeatSuffered, MatchDrawn, NewMatch, TurnStarted, VictoryAchieved},
+    map::MapData,
+    resources::TurnQueue,
+    startup::MatchSetup,
+    states::AppState,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ObjectiveKind {
+    EliminateAllEnemies,
+    // One of the faction's units has to stand on `hex` at the start of `turns`
+    // turns in a row.
+    HoldHex { hex: (i32, i32), turns: u32 },
+    SurviveTurns(u32),
+    // `unit` is the index of the escorted unit in the map's unit list. Losing it
+    // loses the match.
+    EscortUnit { unit: usize, hex: (i32, i32) },
+    // Losing condition: the match is lost once this many turns have passed.
+    TurnLimit(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectiveStatus {
+    InProgress,
+    Complete,
+    Failed,
+}
+
+#[derive(Clone, Debug)]
+pub struct Objective {
+    pub kind: ObjectiveKind,
+    pub status: ObjectiveStatus,
+    // Turns held for `HoldHex`, enemies left for `EliminateAllEnemies`.
+    pub progress: u32,
+}
+
+impl Objective {
+    fn new(kind: ObjectiveKind) -> Self {
+        Objective {
+            kind,
+            status: ObjectiveStatus::InProgress,
+            progress: 0,
+        }
+    }
+
+    // Turn limits are never completed, only failed.
+    pub fn is_victory_condition(&self) -> bool {
+        !matches!(self.kind, ObjectiveKind::TurnLimit(_))
+    }
+
+    pub fn describe(&self, turn_number: i32) -> String {
+        let turns_passed = (turn_number - 1).max(0);
+        let text = match &self.kind {
+            ObjectiveKind::EliminateAllEnemies => {
+                format!("Eliminate all enemies ({} left)", self.progress)
+            }
+            ObjectiveKind::HoldHex { hex, turns } => format!(
+                "Hold {},{} for {} turns ({}/{})",
+                hex.0, hex.1, turns, self.progress, turns
+            ),
+            ObjectiveKind::SurviveTurns(turns) => {
+                format!(
+                    "Survive {} turns ({}/{})",
+                    turns,
+                    turns_passed.min(*turns as i32),
+                    turns
+                )
+            }
+            ObjectiveKind::EscortUnit { hex, .. } => {
+                format!("Escort your unit to {},{}", hex.0, hex.1)
+            }
+            ObjectiveKind::TurnLimit(turns) => {
+                format!("Win within {} turns ({}/{})", turns, turns_passed, turns)
+            }
+        };
+        match self.status {
+            ObjectiveStatus::InProgress => text,
+            ObjectiveStatus::Complete => format!("[Done] {}", text),
+            ObjectiveStatus::Failed => format!("[Failed] {}", text),
+        }
+    }
+}
+
+// Every faction plays for the map's objectives, each on its own account.
+#[derive(Resource, Default)]
+pub struct Objectives(pub HashMap<Faction, Vec<Objective>>);
+
+impl Objectives {
+    pub fn of(&self, faction: Faction) -> &[Objective] {
+        self.0.get(&faction).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn lost(&self, faction: Faction) -> bool {
+        self.of(faction)
+            .iter()
+            .any(|objective| objective.status == ObjectiveStatus::Failed)
+    }
+
+    fn won(&self, faction: Faction) -> bool {
+        let mut victory_conditions = self
+            .of(faction)
+            .iter()
+            .filter(|objective| objective.is_victory_condition())
+            .peekable();
+        victory_conditions.peek().is_some()
+            && victory_conditions.all(|objective| objective.status == ObjectiveStatus::Complete)
+    }
+}
+
+pub struct ObjectivesPlugin;
+
+impl Plugin for ObjectivesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Objectives>()
+            .add_systems(
+                Update,
+                load_objectives
+                    .in_set(MatchSetup)
+                    .run_if(on_event::<NewMatch>()),
+            )
+            .add_systems(
+                Update,
+                (update_objectives, check_match_over)
+                    .chain()
+                    .after(MatchSetup)
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+// An escort is only the objective of the faction whose unit is escorted.
+fn load_objectives(map: Res<MapData>, mut objectives: ResMut<Objectives>) {
+    let factions: BTreeSet<u32> = map.units.iter().map(|unit| unit.faction).collect();
+    objectives.0 = factions
+        .into_iter()
+        .map(Faction)
+        .map(|faction| {
+            let own = map
+                .objectives
+                .iter()
+                .filter(|kind| match kind {
+                    ObjectiveKind::EscortUnit { unit, .. } => map
+                        .units
+                        .get(*unit)
+                        .is_some_and(|unit| Faction(unit.faction) == faction),
+                    _ => true,
+                })
+                .cloned()
+                .map(Objective::new)
+                .collect();
+            (faction, own)
+        })
+        .collect();
+}
+
+fn update_objectives(
+    mut objectives: ResMut<Objectives>,
+    turn_queue: Res<TurnQueue>,
+    mut ev_turn_started: EventReader<TurnStarted>,
+    unit_q: Query<(&BoardLoc, &Faction, Option<&MapUnit>), With<Unit>>,
+) {
+    let turn_started = ev_turn_started.iter().count() > 0;
+    let turns_passed = (turn_queue.turn_number - 1).max(0) as u32;
+
+    for (faction, objectives) in objectives.0.iter_mut() {
+        let enemies_left = unit_q
+            .iter()
+            .filter(|(_, other, _)| *other != faction)
+            .count() as u32;
+        for objective in objectives.iter_mut() {
+            if objective.status != ObjectiveStatus::InProgress {
+                continue;
+            }
+            match objective.kind {
+                ObjectiveKind::EliminateAllEnemies => {
+                    objective.progress = enemies_left;
+                    if enemies_left == 0 {
+                        objective.status = ObjectiveStatus::Complete;
+                    }
+                }
+                ObjectiveKind::HoldHex { hex, turns } => {
+                    if turn_started {
+                        let held = unit_q.iter().any(|(board_loc, other, _)| {
+                            other == faction && board_loc.hex == Hex::new(hex.0, hex.1)
+                        });
+                        objective.progress = if held { objective.progress + 1 } else { 0 };
+                    }
+                    if objective.progress >= turns {
+                        objective.status = ObjectiveStatus::Complete;
+                    }
+                }
+                ObjectiveKind::SurviveTurns(turns) => {
+                    if turns_passed >= turns {
+                        objective.status = ObjectiveStatus::Complete;
+                    }
+                }
+                ObjectiveKind::EscortUnit { unit, hex } => {
+                    match unit_q
+                        .iter()
+                        .find(|(_, _, map_unit)| map_unit.is_some_and(|x| x.0 == unit))
+                    {
+                        Some((board_loc, _, _)) if board_loc.hex == Hex::new(hex.0, hex.1) => {
+                            objective.status = ObjectiveStatus::Complete;
+                        }
+                        Some(_) => (),
+                        None => objective.status = ObjectiveStatus::Failed,
+                    }
+                }
+                ObjectiveKind::TurnLimit(turns) => {
+                    if turns_passed >= turns {
+                        objective.status = ObjectiveStatus::Failed;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A faction loses by failing an objective or losing all its units, and wins by
+// completing its objectives or by being the last one left. The match is over
+// once somebody wins, or everybody has lost. Factions that complete their
+// objectives at the same time draw.
+fn check_match_over(
+    objectives: Res<Objectives>,
+    turn_queue: Res<TurnQueue>,
+    unit_q: Query<&Faction, With<Unit>>,
+    mut ev_victory: EventWriter<VictoryAchieved>,
+    mut ev_defeat: EventWriter<DefeatSuffered>,
+    mut ev_drawn: EventWriter<MatchDrawn>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    // Nothing is decided before the match's units are on the board, or when the
+    // map didn't have any.
+    if unit_q.is_empty() {
+        return;
+    }
+    let lost = |faction: Faction| {
+        objectives.lost(faction) || !unit_q.iter().any(|other| *other == faction)
+    };
+    let standing: Vec<Faction> = turn_queue
+        .factions
+        .iter()
+        .copied()
+        .filter(|faction| !lost(*faction))
+        .collect();
+    let mut winners: Vec<Faction> = standing
+        .iter()
+        .copied()
+        .filter(|faction| objectives.won(*faction))
+        .collect();
+    if winners.is_empty() {
+        match standing.len() {
+            1 if turn_queue.factions.len() > 1 => winners = standing,
+            0 => (),
+            _ => return,
+        }
+    }
+    match winners.as_slice() {
+        [faction] => ev_victory.send(VictoryAchieved(*faction)),
+        [] => (),
+        _ => ev_drawn.send(MatchDrawn(winners.clone())),
+    }
+    for faction in turn_queue.factions.iter().copied() {
+        if !winners.contains(&faction) {
+            ev_defeat.send(DefeatSuffered(faction));
+        }
+    }
+    next_state.set(AppState::GameOver);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two factions playing for the same objective, with a unit each.
+    fn shared_objective(kind: ObjectiveKind) -> App {
+        let mut app = App::new();
+        let factions = vec![Faction(0), Faction(1)];
+        app.add_state::<AppState>()
+            .add_event::<TurnStarted>()
+            .add_event::<VictoryAchieved>()
+            .add_event::<DefeatSuffered>()
+            .add_event::<MatchDrawn>()
+            .insert_resource(TurnQueue::new(factions.clone()))
+            .insert_resource(Objectives(
+                factions
+                    .iter()
+                    .map(|faction| (*faction, vec![Objective::new(kind.clone())]))
+                    .collect(),
+            ))
+            .add_systems(Update, (update_objectives, check_match_over).chain());
+        for (faction, hex) in factions.into_iter().zip([Hex::ZERO, Hex::new(3, 0)]) {
+            app.world.spawn((
+                Unit {
+                    health: 10,
+                    max_health: 10,
+                },
+                faction,
+                BoardLoc { hex },
+            ));
+        }
+        app
+    }
+
+    #[test]
+    fn completing_a_shared_objective_together_is_a_draw() {
+        let mut app = shared_objective(ObjectiveKind::SurviveTurns(2));
+        app.update();
+        assert!(app.world.resource::<Events<MatchDrawn>>().is_empty());
+
+        app.world.resource_mut::<TurnQueue>().turn_number = 3;
+        app.update();
+        let drawn: Vec<Vec<Faction>> = app
+            .world
+            .resource_mut::<Events<MatchDrawn>>()
+            .drain()
+            .map(|ev| ev.0)
+            .collect();
+        assert_eq!(drawn, vec![vec![Faction(0), Faction(1)]]);
+        assert!(app.world.resource::<Events<VictoryAchieved>>().is_empty());
+        assert!(app.world.resource::<Events<DefeatSuffered>>().is_empty());
+        assert_eq!(
+            app.world.resource::<NextState<AppState>>().0,
+            Some(AppState::GameOver)
+        );
+    }
+}
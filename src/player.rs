@@ -1,11 +1,15 @@
 use bevy::prelude::*;
 
 use crate::{
-    components::{Activated, BaseHex, MoveTarget, Moving, Selected, Unit},
+    components::{
+        Activated, BaseHex, MoveTarget, Moving, PathPreview, Selected, Unit, ZoneOfControlStop,
+    },
     events::{ClearLastClicked, ClickedOutsideActivationRange, UnitDeselected, UnitSelected},
     states::PlayerState,
 };
 
+type SelectedUnit = (With<Unit>, With<Selected>);
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
@@ -14,10 +18,13 @@ impl Plugin for PlayerPlugin {
             Update,
             (
                 transition_to_select_unit_state,
-                transition_to_idle_state,
+                // Units are also deselected when they start moving or aiming.
+                transition_to_idle_state.run_if(in_state(PlayerState::UnitSelected)),
                 transition_to_unit_moving_state,
                 on_unit_stop_moving,
-            ),
+            )
+                // Only the next player can dismiss the hand over screen.
+                .run_if(not(in_state(PlayerState::PassingDevice))),
         )
         .add_systems(
             OnExit(PlayerState::UnitSelected),
@@ -26,18 +33,20 @@ impl Plugin for PlayerPlugin {
     }
 }
 
+// Selecting another unit while one is selected switches straight to it.
 fn transition_to_select_unit_state(
+    state: Res<State<PlayerState>>,
     mut next_state: ResMut<NextState<PlayerState>>,
     mut ev_unit_selected: EventReader<UnitSelected>,
 ) {
-    for _ in ev_unit_selected.iter() {
+    if ev_unit_selected.iter().count() > 0 && *state.get() != PlayerState::UnitSelected {
         next_state.set(PlayerState::UnitSelected);
     }
 }
 
 fn transition_to_idle_state(
     mut next_state: ResMut<NextState<PlayerState>>,
-    unit_q: Query<Entity, (With<Unit>, With<Selected>)>,
+    unit_q: Query<Entity, SelectedUnit>,
     mut ev_unit_deselected: EventReader<UnitDeselected>,
     ev_clicked_outside_activation_range: EventReader<ClickedOutsideActivationRange>,
 ) {
@@ -76,7 +85,7 @@ fn clear_last_clicked(mut ev_clear_last_clicked: EventWriter<ClearLastClicked>)
 
 fn deactivate_units_and_tiles(
     mut commands: Commands,
-    unit_q: Query<Entity, (With<Unit>, With<Activated>, With<Selected>)>,
+    unit_q: Query<Entity, SelectedUnit>,
     tile_q: Query<Entity, With<BaseHex>>,
 ) {
     for unit in unit_q.iter() {
@@ -90,6 +99,8 @@ fn deactivate_units_and_tiles(
             .entity(tile)
             .remove::<Selected>()
             .remove::<Activated>()
-            .remove::<MoveTarget>();
+            .remove::<MoveTarget>()
+            .remove::<PathPreview>()
+            .remove::<ZoneOfControlStop>();
     }
 }
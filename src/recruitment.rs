@@ -0,0 +1,325 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use hexx::Hex;
+
+use crate::{
+    abilities::AbilityDefs,
+    archetypes::UnitArchetypes,
+    components::{Faction, Recruited},
+    events::{CommandSubmitted, MouseClickedHex, NewMatch, UnitRecruited},
+    game_command::{BoardState, GameCommand},
+    helpers::board::Board,
+    hot_seat::Players,
+    map::MapData,
+    replay::ReplayPlayback,
+    resources::{Treasury, TurnQueue},
+    rng::RngStream,
+    rules::MatchState,
+    startup::{spawn_unit, MatchSetup},
+    states::{AppState, PlayerState},
+    structures::Structures,
+};
+
+const RECRUIT_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_RECRUIT_BUTTON: Color = Color::rgb(0.3, 0.3, 0.3);
+const UNAFFORDABLE: Color = Color::rgb(0.5, 0.5, 0.5);
+
+// The recruiting structure the panel was opened for.
+#[derive(Resource, Default)]
+pub struct RecruitingAt(pub Option<Hex>);
+
+#[derive(Component)]
+pub struct RecruitPanel;
+
+// The command the button gives, if the faction can recruit that unit now.
+#[derive(Component)]
+pub struct RecruitButton(pub Option<GameCommand>);
+
+pub struct RecruitmentPlugin;
+
+impl Plugin for RecruitmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Recruited>()
+            .init_resource::<Treasury>()
+            .init_resource::<RecruitingAt>()
+            .add_systems(
+                Update,
+                reset_treasury
+                    .in_set(MatchSetup)
+                    .run_if(on_event::<NewMatch>()),
+            )
+            .add_systems(Update, spawn_recruits.after(MatchSetup))
+            .add_systems(
+                Update,
+                open_recruit_panel
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(in_state(PlayerState::Idle))
+                    .run_if(not(resource_exists::<ReplayPlayback>())),
+            )
+            .add_systems(OnEnter(PlayerState::Recruiting), spawn_recruit_panel)
+            .add_systems(
+                Update,
+                (press_recruit_button, close_recruit_panel)
+                    .chain()
+                    .run_if(in_state(PlayerState::Recruiting)),
+            )
+            .add_systems(OnExit(PlayerState::Recruiting), despawn_recruit_panel);
+    }
+}
+
+// Recruits `faction` buys with its gold: the priciest units it can afford, one
+// after another, at every structure it can recruit from. Ties between units
+// that cost the same are broken with `rng`. Plays them on `state` as it goes.
+pub fn plan_recruits(
+    state: &mut MatchState,
+    faction: Faction,
+    ability_defs: &AbilityDefs,
+    rng: &mut RngStream,
+) -> Vec<GameCommand> {
+    let mut archetypes: Vec<(u32, String)> = state
+        .archetypes
+        .0
+        .values()
+        .map(|archetype| (archetype.cost, archetype.name.clone()))
+        .collect();
+    archetypes.sort();
+    let recruiters: Vec<Hex> = state
+        .structures
+        .0
+        .iter()
+        .filter(|structure| structure.def.recruits && structure.owner == Some(faction))
+        .map(|structure| structure.hex)
+        .collect();
+    let mut commands = Vec::new();
+    for structure in recruiters {
+        while let Some(at) = state.recruit_hex(structure) {
+            let affordable: Vec<(u32, GameCommand)> = archetypes
+                .iter()
+                .map(|(cost, name)| {
+                    let command = GameCommand::Recruit {
+                        archetype: name.clone(),
+                        at: (at.x, at.y),
+                    };
+                    (*cost, command)
+                })
+                .filter(|(_, command)| state.validate(faction, command, ability_defs).is_ok())
+                .collect();
+            let Some(best) = affordable.iter().map(|(cost, _)| *cost).max() else {
+                break;
+            };
+            let priciest: Vec<&GameCommand> = affordable
+                .iter()
+                .filter(|(cost, _)| *cost == best)
+                .map(|(_, command)| command)
+                .collect();
+            let Some(command) = rng.pick(&priciest).map(|command| (*command).clone()) else {
+                break;
+            };
+            state.apply(&command, ability_defs);
+            commands.push(command);
+        }
+    }
+    commands
+}
+
+fn reset_treasury(map: Res<MapData>, mut treasury: ResMut<Treasury>) {
+    *treasury = Treasury::from_map(&map);
+}
+
+fn spawn_recruits(
+    mut commands: Commands,
+    mut ev_unit_recruited: EventReader<UnitRecruited>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    archetypes: Res<UnitArchetypes>,
+    board: Board,
+) {
+    for ev in ev_unit_recruited.iter() {
+        let Some(archetype) = archetypes.0.get(&ev.archetype) else {
+            error!("Unknown unit archetype {}", ev.archetype);
+            continue;
+        };
+        let unit = spawn_unit(
+            &mut commands,
+            &asset_server,
+            &mut texture_atlases,
+            archetype,
+            ev.hex,
+            ev.faction,
+            &board,
+        );
+        commands
+            .entity(unit)
+            .insert((ev.unit, Recruited(ev.turn_number)));
+    }
+}
+
+// Clicking a free recruiting structure of the human player whose turn it is
+// opens its recruit panel.
+fn open_recruit_panel(
+    mut ev_mouse_clicked_hex: EventReader<MouseClickedHex>,
+    turn_queue: Res<TurnQueue>,
+    players: Res<Players>,
+    structures: Res<Structures>,
+    board_state: BoardState,
+    mut recruiting_at: ResMut<RecruitingAt>,
+    mut next_state: ResMut<NextState<PlayerState>>,
+) {
+    let Some(clicked) = ev_mouse_clicked_hex.iter().last() else {
+        return;
+    };
+    let faction = turn_queue.active_faction();
+    let recruiter = structures
+        .at(clicked.0)
+        .is_some_and(|structure| structure.def.recruits && structure.owner == Some(faction));
+    if !recruiter || !players.is_human(faction) {
+        return;
+    }
+    if board_state.snapshot().occupant(clicked.0).is_some() {
+        return;
+    }
+    recruiting_at.0 = Some(clicked.0);
+    next_state.set(PlayerState::Recruiting);
+}
+
+fn spawn_recruit_panel(
+    mut commands: Commands,
+    recruiting_at: Res<RecruitingAt>,
+    board_state: BoardState,
+    ability_defs: Res<AbilityDefs>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Some(structure) = recruiting_at.0 else {
+        return;
+    };
+    let state = board_state.snapshot();
+    let faction = state.turn_queue.active_faction();
+    let at = state.recruit_hex(structure);
+    let mut archetypes: Vec<(u32, String)> = state
+        .archetypes
+        .0
+        .values()
+        .map(|archetype| (archetype.cost, archetype.name.clone()))
+        .collect();
+    archetypes.sort();
+    let position = window_q
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .unwrap_or_default();
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(position.x),
+                    top: Val::Px(position.y),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    row_gap: Val::Px(2.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                z_index: ZIndex::Global(50),
+                ..default()
+            },
+            RecruitPanel,
+            Name::new("Recruit Panel"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                format!("Recruit - {} gold", state.treasury.gold(faction)),
+                TextStyle {
+                    font_size: 18.0,
+                    ..default()
+                },
+            ));
+            for (cost, name) in archetypes {
+                let command = at
+                    .map(|at| GameCommand::Recruit {
+                        archetype: name.clone(),
+                        at: (at.x, at.y),
+                    })
+                    .filter(|command| state.validate(faction, command, &ability_defs).is_ok());
+                let color = if command.is_some() {
+                    Color::WHITE
+                } else {
+                    UNAFFORDABLE
+                };
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                min_width: Val::Px(160.0),
+                                padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                                ..default()
+                            },
+                            background_color: RECRUIT_BUTTON.into(),
+                            ..default()
+                        },
+                        RecruitButton(command),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            format!("{} - {} gold", name, cost),
+                            TextStyle {
+                                font_size: 18.0,
+                                color,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn press_recruit_button(
+    mut button_q: Query<(&Interaction, &RecruitButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut ev_command_submitted: EventWriter<CommandSubmitted>,
+    mut next_state: ResMut<NextState<PlayerState>>,
+) {
+    for (interaction, button, mut color) in button_q.iter_mut() {
+        match interaction {
+            Interaction::Pressed => (),
+            Interaction::Hovered => {
+                *color = HOVERED_RECRUIT_BUTTON.into();
+                continue;
+            }
+            Interaction::None => {
+                *color = RECRUIT_BUTTON.into();
+                continue;
+            }
+        }
+        // Units that can't be recruited right now are only listed.
+        if let Some(command) = &button.0 {
+            ev_command_submitted.send(CommandSubmitted(command.clone()));
+            next_state.set(PlayerState::Idle);
+        }
+    }
+}
+
+// Right-clicking, or clicking anywhere off the panel, backs out of it.
+fn close_recruit_panel(
+    mouse_input: Res<Input<MouseButton>>,
+    button_q: Query<&Interaction, With<RecruitButton>>,
+    mut next_state: ResMut<NextState<PlayerState>>,
+) {
+    let on_panel = button_q
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if mouse_input.just_pressed(MouseButton::Right)
+        || (mouse_input.just_pressed(MouseButton::Left) && !on_panel)
+    {
+        next_state.set(PlayerState::Idle);
+    }
+}
+
+fn despawn_recruit_panel(
+    mut commands: Commands,
+    panel_q: Query<Entity, With<RecruitPanel>>,
+    mut recruiting_at: ResMut<RecruitingAt>,
+) {
+    recruiting_at.0 = None;
+    for entity in panel_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
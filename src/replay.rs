@@ -0,0 +1,320 @@
+use std::fs;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::Moving,
+    events::{CommandAccepted, CommandSubmitted, NewMatch},
+    game_command::GameCommand,
+    map::MapData,
+    network::NetSession,
+    rng::GameRng,
+    states::{AppState, PlayerState},
+};
+
+// Replays are written to the working directory, like settings. The last match
+// played is always kept.
+const REPLAY_DIR: &str = "replays";
+const LAST_REPLAY: &str = "replays/last.ron";
+
+// Time between commands when playing back at normal speed.
+const COMMAND_INTERVAL: f32 = 0.8;
+const PLAYBACK_SPEEDS: [f32; 4] = [0.5, 1.0, 2.0, 4.0];
+const NORMAL_SPEED: usize = 1;
+
+// Everything needed to play a match again: it starts from `map` with `seed`,
+// and the same commands are submitted in the same order.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub map: MapData,
+    pub commands: Vec<GameCommand>,
+    // The RNG as it was when the replay was saved. Playing every command
+    // should leave it in the same state, or the replay has desynced.
+    #[serde(default)]
+    pub rng: Option<GameRng>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Option<Replay> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                error!("Could not read replay {}: {}", path, err);
+                return None;
+            }
+        };
+        match ron::from_str(&contents) {
+            Ok(replay) => Some(replay),
+            Err(err) => {
+                error!("Could not parse replay {}: {}", path, err);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> bool {
+        let contents = match ron::ser::to_string_pretty(self, default()) {
+            Ok(contents) => contents,
+            Err(err) => {
+                error!("Could not serialize replay: {}", err);
+                return false;
+            }
+        };
+        if let Err(err) = fs::create_dir_all(REPLAY_DIR).and_then(|_| fs::write(path, contents)) {
+            error!("Could not save replay to {}: {}", path, err);
+            return false;
+        }
+        true
+    }
+}
+
+// The match being played, recorded as it goes.
+#[derive(Resource, Default)]
+pub struct Recording(pub Replay);
+
+// Present while a replay is playing. Player input is ignored until it's done.
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub commands: Vec<GameCommand>,
+    pub expected_rng: Option<GameRng>,
+    // Index of the next command to submit.
+    pub next: usize,
+    pub paused: bool,
+    // Index into `PLAYBACK_SPEEDS`.
+    pub speed: usize,
+    // Submits the next command even though playback is paused.
+    pub step: bool,
+    // Hands the match over to the player, from wherever it got to.
+    pub stop: bool,
+    pub timer: Timer,
+}
+
+impl ReplayPlayback {
+    fn new(replay: Replay) -> Self {
+        ReplayPlayback {
+            commands: replay.commands,
+            expected_rng: replay.rng,
+            next: 0,
+            paused: false,
+            speed: NORMAL_SPEED,
+            step: false,
+            stop: false,
+            timer: Timer::from_seconds(COMMAND_INTERVAL, TimerMode::Once),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ReplayPanel;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recording>()
+            .add_systems(Update, (start_recording, record_commands).chain())
+            .add_systems(OnEnter(AppState::GameOver), save_recording)
+            .add_systems(
+                Update,
+                (
+                    save_recording_on_key,
+                    // The other player in a network match has no replay to follow.
+                    start_playback.run_if(not(resource_exists::<NetSession>())),
+                )
+                    .run_if(in_state(AppState::InGame).or_else(in_state(AppState::GameOver)))
+                    .run_if(not(resource_exists::<ReplayPlayback>())),
+            )
+            .add_systems(
+                Update,
+                spawn_replay_panel.run_if(resource_added::<ReplayPlayback>()),
+            )
+            .add_systems(
+                Update,
+                (playback_controls, update_replay_panel, play_commands)
+                    .chain()
+                    .run_if(resource_exists::<ReplayPlayback>()),
+            );
+    }
+}
+
+fn start_recording(
+    mut ev_new_match: EventReader<NewMatch>,
+    map: Res<MapData>,
+    mut recording: ResMut<Recording>,
+) {
+    if let Some(ev) = ev_new_match.iter().last() {
+        recording.0 = Replay {
+            seed: ev.seed,
+            map: map.clone(),
+            commands: Vec::new(),
+            rng: None,
+        };
+    }
+}
+
+// Only commands that were played are recorded. Commands submitted by a replay
+// are recorded too, so a match picked up where a replay was stopped is
+// recorded in full.
+fn record_commands(
+    mut ev_command_accepted: EventReader<CommandAccepted>,
+    mut recording: ResMut<Recording>,
+) {
+    for ev in ev_command_accepted.iter() {
+        recording.0.commands.push(ev.0.clone());
+    }
+}
+
+fn save_recording(recording: Res<Recording>, rng: Res<GameRng>) {
+    let replay = Replay {
+        rng: Some(*rng),
+        ..recording.0.clone()
+    };
+    if replay.save(LAST_REPLAY) {
+        info!("Saved replay to {}", LAST_REPLAY);
+    }
+}
+
+fn save_recording_on_key(
+    keyboard_input: Res<Input<KeyCode>>,
+    recording: Res<Recording>,
+    rng: Res<GameRng>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        save_recording(recording, rng);
+    }
+}
+
+fn start_playback(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut map: ResMut<MapData>,
+    mut ev_new_match: EventWriter<NewMatch>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut next_player_state: ResMut<NextState<PlayerState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F7) {
+        return;
+    }
+    let Some(replay) = Replay::load(LAST_REPLAY) else {
+        return;
+    };
+    // The replayed map is played on, but editing it still saves to the
+    // current map's file.
+    *map = MapData {
+        path: map.path.clone(),
+        ..replay.map.clone()
+    };
+    ev_new_match.send(NewMatch { seed: replay.seed });
+    commands.insert_resource(ReplayPlayback::new(replay));
+    next_app_state.set(AppState::InGame);
+    next_player_state.set(PlayerState::Idle);
+}
+
+fn playback_controls(keyboard_input: Res<Input<KeyCode>>, mut playback: ResMut<ReplayPlayback>) {
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        playback.paused = !playback.paused;
+    }
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        playback.step = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::Equals) {
+        playback.speed = (playback.speed + 1).min(PLAYBACK_SPEEDS.len() - 1);
+    }
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        playback.speed = playback.speed.saturating_sub(1);
+    }
+    if keyboard_input.just_pressed(KeyCode::F8) {
+        playback.stop = true;
+    }
+}
+
+fn play_commands(
+    mut commands: Commands,
+    time: Res<Time>,
+    rng: Res<GameRng>,
+    mut playback: ResMut<ReplayPlayback>,
+    moving_q: Query<(), With<Moving>>,
+    panel_q: Query<Entity, With<ReplayPanel>>,
+    mut ev_command_submitted: EventWriter<CommandSubmitted>,
+) {
+    if playback.stop {
+        stop_playback(&mut commands, &panel_q);
+        return;
+    }
+    // Commands only ever came in once units had stopped, so they're replayed
+    // the same way.
+    if !moving_q.is_empty() {
+        return;
+    }
+    let speed = PLAYBACK_SPEEDS[playback.speed];
+    playback.timer.tick(time.delta().mul_f32(speed));
+    let ready = if playback.paused {
+        playback.step
+    } else {
+        playback.timer.finished() || playback.step
+    };
+    if !ready {
+        return;
+    }
+    let Some(command) = playback.commands.get(playback.next).cloned() else {
+        if playback
+            .expected_rng
+            .is_some_and(|expected| !expected.replays_like(&rng))
+        {
+            warn!(
+                "Replay desynced: the RNG ended up in a different state than when it was recorded"
+            );
+        }
+        stop_playback(&mut commands, &panel_q);
+        return;
+    };
+    ev_command_submitted.send(CommandSubmitted(command));
+    playback.next += 1;
+    playback.step = false;
+    playback.timer.reset();
+}
+
+fn stop_playback(commands: &mut Commands, panel_q: &Query<Entity, With<ReplayPanel>>) {
+    commands.remove_resource::<ReplayPlayback>();
+    for entity in panel_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_replay_panel(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 18.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        }),
+        ReplayPanel,
+        Name::new("Replay Panel"),
+    ));
+}
+
+fn update_replay_panel(
+    playback: Res<ReplayPlayback>,
+    mut text_q: Query<&mut Text, With<ReplayPanel>>,
+) {
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = format!(
+            "Replay {}/{} {} at {}x\nSpace to {}, . to step\n- and = to change speed\nF8 to take over",
+            playback.next,
+            playback.commands.len(),
+            if playback.paused { "paused" } else { "playing" },
+            PLAYBACK_SPEEDS[playback.speed],
+            if playback.paused { "play" } else { "pause" },
+        );
+    }
+}
@@ -1,19 +1,238 @@
-use bevy::{prelude::*, utils::HashSet};
-use hexx::Hex;
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use hexx::{Hex, HexLayout, HexOrientation};
 
-#[derive(Resource)]
+use crate::{
+    components::{Faction, UnitId},
+    constants::PLAYER_FACTION,
+    map::{LayoutConfig, MapData, Orientation, Terrain},
+};
+
+#[derive(Resource, Clone)]
 pub struct TurnQueue {
     pub turn_number: i32,
+    // Factions in the order they take their turns. A turn number covers one
+    // turn of every faction.
+    pub factions: Vec<Faction>,
+    // Index into `factions` of the faction whose turn it is.
+    pub active: usize,
+}
+
+impl TurnQueue {
+    pub fn new(factions: Vec<Faction>) -> Self {
+        TurnQueue {
+            factions,
+            ..default()
+        }
+    }
+
+    pub fn active_faction(&self) -> Faction {
+        self.factions
+            .get(self.active)
+            .copied()
+            .unwrap_or(PLAYER_FACTION)
+    }
+
+    // Hands the turn to the next faction that still has units. Returns the new
+    // turn number if every faction has now had its turn.
+    pub fn pass(&mut self, has_units: impl Fn(Faction) -> bool) -> Option<i32> {
+        let mut started = None;
+        for _ in 0..self.factions.len() {
+            self.active += 1;
+            if self.active >= self.factions.len() {
+                self.active = 0;
+                self.turn_number += 1;
+                started = Some(self.turn_number);
+            }
+            if has_units(self.active_faction()) {
+                break;
+            }
+        }
+        started
+    }
 }
 
 #[derive(Resource)]
 pub struct CursorPos(pub Vec2);
 
+// Seed of the match being played. Replays store it so they play out the same.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct MatchSeed(pub u64);
+
 #[derive(Resource, Default)]
+pub struct NextUnitId(pub u32);
+
+impl NextUnitId {
+    pub fn take(&mut self) -> UnitId {
+        let id = UnitId(self.0);
+        self.0 += 1;
+        id
+    }
+}
+
+// Gold each faction has to spend.
+#[derive(Resource, Default, Clone, Debug)]
+pub struct Treasury(pub HashMap<Faction, u32>);
+
+impl Treasury {
+    // Every faction with units on the map starts with the map's starting gold.
+    pub fn from_map(map: &MapData) -> Self {
+        Treasury(
+            map.units
+                .iter()
+                .map(|unit| (Faction(unit.faction), map.starting_gold))
+                .collect(),
+        )
+    }
+
+    pub fn gold(&self, faction: Faction) -> u32 {
+        self.0.get(&faction).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, faction: Faction, gold: u32) {
+        self.0.insert(faction, gold);
+    }
+}
+
+#[derive(Resource, Default, Clone)]
 pub struct HexMap(pub HashSet<Hex>);
 
+impl HexMap {
+    // The hex nearest the middle of the board, wherever the map was drawn.
+    pub fn center(&self) -> Hex {
+        if self.0.is_empty() {
+            return Hex::ZERO;
+        }
+        let sum = self
+            .0
+            .iter()
+            .fold(IVec2::ZERO, |sum, hex| sum + IVec2::new(hex.x, hex.y));
+        let count = self.0.len() as f32;
+        Hex::round([sum.x as f32 / count, sum.y as f32 / count])
+    }
+}
+
+// Height of each hex, in levels. Hexes that aren't listed are at level 0.
+#[derive(Resource, Default, Clone)]
+pub struct Elevation(pub HashMap<Hex, u32>);
+
+impl Elevation {
+    pub fn from_map(map: &MapData) -> Self {
+        Elevation(
+            map.elevation
+                .iter()
+                .map(|(&(x, y), &level)| (Hex::new(x, y), level))
+                .collect(),
+        )
+    }
+
+    pub fn level(&self, hex: Hex) -> u32 {
+        self.0.get(&hex).copied().unwrap_or_default()
+    }
+
+    pub fn highest(&self) -> u32 {
+        self.0.values().copied().max().unwrap_or_default()
+    }
+}
+
+#[derive(Resource, Default, Clone)]
+pub struct TerrainMap(pub HashMap<Hex, Terrain>);
+
+impl TerrainMap {
+    pub fn from_map(map: &MapData) -> Self {
+        TerrainMap(
+            map.terrain
+                .iter()
+                .map(|(&(x, y), &terrain)| (Hex::new(x, y), terrain))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, hex: Hex) -> Terrain {
+        self.0.get(&hex).copied().unwrap_or_default()
+    }
+}
+
+// The loaded map's grid geometry. Go through `helpers::board::Board` to convert
+// between hexes and world positions, so elevation is taken into account.
+#[derive(Resource, Clone, Debug)]
+pub struct MapLayout {
+    pub layout: HexLayout,
+    // Size tile sprites are drawn at, so tile art of any resolution fills a hex.
+    pub tile_size: Vec2,
+    pub tile_texture: String,
+}
+
+impl MapLayout {
+    pub fn from_config(config: &LayoutConfig) -> Self {
+        let hex_size = Vec2::new(config.hex_size.0, config.hex_size.1);
+        let (orientation, tile_size) = match config.orientation {
+            Orientation::Flat => (
+                HexOrientation::Flat,
+                Vec2::new(2.0 * hex_size.x, 3.0_f32.sqrt() * hex_size.y),
+            ),
+            Orientation::Pointy => (
+                HexOrientation::Pointy,
+                Vec2::new(3.0_f32.sqrt() * hex_size.x, 2.0 * hex_size.y),
+            ),
+        };
+        MapLayout {
+            layout: HexLayout {
+                hex_size,
+                orientation,
+                origin: Vec2::new(config.origin.0, config.origin.1),
+                invert_x: false,
+                invert_y: false,
+            },
+            tile_size,
+            tile_texture: config.tile_texture.clone(),
+        }
+    }
+}
+
+impl Default for MapLayout {
+    fn default() -> Self {
+        MapLayout::from_config(&LayoutConfig::default())
+    }
+}
+
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, PartialEq)]
+#[reflect(Resource)]
+pub enum AnimationSpeed {
+    #[default]
+    Normal,
+    Fast,
+    Instant,
+}
+
+impl AnimationSpeed {
+    // How far a unit travels along its path each second, in world units.
+    // `None` means movement happens in a single frame.
+    pub fn units_per_second(&self) -> Option<f32> {
+        match self {
+            AnimationSpeed::Normal => Some(120.0),
+            AnimationSpeed::Fast => Some(360.0),
+            AnimationSpeed::Instant => None,
+        }
+    }
+
+    pub fn next(&self) -> AnimationSpeed {
+        match self {
+            AnimationSpeed::Normal => AnimationSpeed::Fast,
+            AnimationSpeed::Fast => AnimationSpeed::Instant,
+            AnimationSpeed::Instant => AnimationSpeed::Normal,
+        }
+    }
+}
+
 impl Default for TurnQueue {
     fn default() -> Self {
-        TurnQueue { turn_number: 1 }
+        TurnQueue {
+            turn_number: 1,
+            factions: vec![PLAYER_FACTION],
+            active: 0,
+        }
     }
 }
@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{events::NewMatch, startup::MatchSetup};
+
+// Mixed into the match seed so each stream gets a sequence of its own.
+const AI_STREAM: u64 = 0x6169;
+const MAPGEN_STREAM: u64 = 0x6d61_7067_656e;
+
+// A small SplitMix64 generator. Its whole state is one number, and it gives the
+// same numbers on every platform.
+#[derive(Serialize, Deserialize, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RngStream {
+    state: u64,
+}
+
+impl RngStream {
+    pub fn new(seed: u64) -> Self {
+        RngStream { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get((self.next_u64() % items.len() as u64) as usize)
+    }
+}
+
+// All gameplay randomness comes from here, never from `thread_rng` (clippy and
+// a test reject it). Each subsystem draws from its own stream, so adding a roll
+// to one doesn't change what the others get. Combat has no rolls yet, and gets a
+// stream once it does.
+#[derive(Resource, Serialize, Deserialize, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Resource)]
+pub struct GameRng {
+    pub ai: RngStream,
+    pub mapgen: RngStream,
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        let stream = |salt: u64| RngStream::new(RngStream::new(seed ^ salt).next_u64());
+        GameRng {
+            ai: stream(AI_STREAM),
+            mapgen: stream(MAPGEN_STREAM),
+        }
+    }
+
+    // Whether `other` is in the same state for everything a replay plays back.
+    // The AI's stream is left out, as replays carry the AI's commands instead
+    // of having it plan them again.
+    pub fn replays_like(&self, other: &GameRng) -> bool {
+        self.mapgen == other.mapgen
+    }
+}
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameRng>()
+            .register_type::<GameRng>()
+            .add_systems(
+                Update,
+                reseed.in_set(MatchSetup).run_if(on_event::<NewMatch>()),
+            );
+    }
+}
+
+fn reseed(mut ev_new_match: EventReader<NewMatch>, mut rng: ResMut<GameRng>) {
+    if let Some(ev) = ev_new_match.iter().last() {
+        *rng = GameRng::from_seed(ev.seed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    fn draws(stream: &mut RngStream) -> Vec<u64> {
+        (0..8).map(|_| stream.next_u64()).collect()
+    }
+
+    #[test]
+    fn pinned_seed_gives_the_same_numbers() {
+        let mut a = GameRng::from_seed(1234);
+        let mut b = GameRng::from_seed(1234);
+        assert_eq!(a, b);
+        assert_eq!(draws(&mut a.ai), draws(&mut b.ai));
+        assert_eq!(draws(&mut a.mapgen), draws(&mut b.mapgen));
+    }
+
+    #[test]
+    fn different_seeds_give_different_numbers() {
+        let mut a = GameRng::from_seed(1);
+        let mut b = GameRng::from_seed(2);
+        assert_ne!(draws(&mut a.ai), draws(&mut b.ai));
+    }
+
+    #[test]
+    fn streams_are_independent() {
+        let mut rng = GameRng::from_seed(99);
+        assert_ne!(rng.ai, rng.mapgen);
+        let mapgen = rng.mapgen;
+        draws(&mut rng.ai);
+        assert_eq!(rng.mapgen, mapgen);
+    }
+
+    #[test]
+    fn pick_stays_in_range() {
+        let mut stream = RngStream::new(7);
+        let items = [1, 2, 3];
+        for _ in 0..100 {
+            assert!(items.contains(stream.pick(&items).unwrap()));
+        }
+        assert_eq!(stream.pick::<u8>(&[]), None);
+    }
+
+    // clippy.toml only catches these once `rand` is a dependency.
+    #[test]
+    fn thread_rng_is_never_called() {
+        let mut dirs = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("src")];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let source = fs::read_to_string(&path).unwrap();
+                for banned in ["thread_rng", "rand::random"] {
+                    assert!(
+                        !source.contains(&format!("{}(", banned)),
+                        "{} calls {}",
+                        path.display(),
+                        banned
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn new_match_reseeds_from_its_seed() {
+        let mut app = App::new();
+        app.add_event::<NewMatch>().add_plugins(RngPlugin);
+        app.world.send_event(NewMatch { seed: 42 });
+        app.update();
+        assert_eq!(*app.world.resource::<GameRng>(), GameRng::from_seed(42));
+    }
+}
@@ -0,0 +1,1190 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+};
+
+use hexx::{Direction, Hex};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    abilities::{
+        ability_damage, can_use_ability, AbilityCooldowns, AbilityDef, AbilityDefs, AbilityEffect,
+    },
+    archetypes::{UnitArchetype, UnitArchetypes, UnitTrait},
+    components::{ActionPoints, Attack, Defense, EffectiveStats, Faction, MoveRange, UnitId},
+    facing::{attack_angle, direction_index, facing_towards, starting_facing},
+    game_command::GameCommand,
+    helpers::unit::{find_path, movement_range, path_cost, ZoneOfControl},
+    line_of_sight::Sight,
+    map::MapData,
+    resources::{Elevation, HexMap, TerrainMap, Treasury, TurnQueue},
+    status_effects::{StatusEffect, StatusEffects},
+    structures::{StructureDefs, Structures},
+};
+
+// Why a command was turned down.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Rejection {
+    UnknownUnit(UnitId),
+    NotYourTurn,
+    NotYourUnit,
+    NotAnEnemy,
+    OffBoard,
+    OutOfRange,
+    NoLineOfSight,
+    Occupied,
+    Stunned,
+    UnknownAbility(String),
+    // None of the unit's abilities can hurt the target.
+    NoAttack,
+    NotEnoughActionPoints { needed: u32, left: u32 },
+    // The unit has already moved too far this turn.
+    NotEnoughMovement { needed: u32, left: u32 },
+    OnCooldown { turns: u32 },
+    UnknownArchetype(String),
+    // None of the faction's recruiting structures are next to the hex.
+    NoRecruiter,
+    NotEnoughGold { needed: u32, left: u32 },
+    JustRecruited,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejection::UnknownUnit(id) => write!(f, "there's no unit {}", id.0),
+            Rejection::NotYourTurn => write!(f, "it's not your turn"),
+            Rejection::NotYourUnit => write!(f, "that unit isn't yours"),
+            Rejection::NotAnEnemy => write!(f, "that unit is on your side"),
+            Rejection::OffBoard => write!(f, "that hex isn't on the board"),
+            Rejection::OutOfRange => write!(f, "that hex is out of range"),
+            Rejection::NoLineOfSight => write!(f, "the unit can't see that hex"),
+            Rejection::Occupied => write!(f, "that hex is taken"),
+            Rejection::Stunned => write!(f, "the unit is stunned"),
+            Rejection::UnknownAbility(name) => write!(f, "the unit can't use {}", name),
+            Rejection::NoAttack => write!(f, "the unit has no way to attack that"),
+            Rejection::NotEnoughActionPoints { needed, left } => {
+                write!(f, "it needs {} action points, and has {}", needed, left)
+            }
+            Rejection::NotEnoughMovement { needed, left } => {
+                write!(f, "it needs {} movement, and has {} left", needed, left)
+            }
+            Rejection::OnCooldown { turns } => write!(f, "it's ready again in {} turns", turns),
+            Rejection::UnknownArchetype(name) => write!(f, "there's no such unit as {}", name),
+            Rejection::NoRecruiter => write!(f, "you have nowhere to recruit that hex from"),
+            Rejection::NotEnoughGold { needed, left } => {
+                write!(f, "it costs {} gold, and you have {}", needed, left)
+            }
+            Rejection::JustRecruited => write!(f, "the unit was only just recruited"),
+        }
+    }
+}
+
+// Something that changed in a match, as the result of a command. Values are
+// what they changed to, so they can be applied to a board as they are.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum StateChange {
+    UnitMoved {
+        unit: UnitId,
+        to: (i32, i32),
+    },
+    UnitAttacked {
+        attacker: UnitId,
+        target: UnitId,
+    },
+    // The unit died if it's at 0 or less.
+    Health {
+        unit: UnitId,
+        health: i32,
+    },
+    StatusEffects {
+        unit: UnitId,
+        effects: Vec<StatusEffect>,
+    },
+    ActionPoints {
+        unit: UnitId,
+        current: u32,
+    },
+    MovementSpent {
+        unit: UnitId,
+        spent: u32,
+    },
+    Cooldowns {
+        unit: UnitId,
+        cooldowns: Vec<(String, u32)>,
+    },
+    // An index into `Direction::ALL_DIRECTIONS`.
+    Facing {
+        unit: UnitId,
+        facing: u8,
+    },
+    StructureCaptured {
+        hex: (i32, i32),
+        faction: u32,
+    },
+    Gold {
+        faction: u32,
+        gold: u32,
+    },
+    UnitRecruited {
+        unit: UnitId,
+        archetype: String,
+        faction: u32,
+        hex: (i32, i32),
+    },
+    TurnPassed {
+        turn_number: i32,
+        faction: u32,
+    },
+}
+
+// A unit, as far as the rules are concerned.
+#[derive(Clone, Debug)]
+pub struct UnitState {
+    pub id: UnitId,
+    pub faction: Faction,
+    pub hex: Hex,
+    pub health: i32,
+    pub max_health: i32,
+    pub move_range: u32,
+    pub attack: i32,
+    pub defense: i32,
+    pub action_points: ActionPoints,
+    // Movement used up this turn.
+    pub movement_spent: u32,
+    pub abilities: Vec<String>,
+    pub cooldowns: AbilityCooldowns,
+    pub status_effects: StatusEffects,
+    pub skirmisher: bool,
+    pub facing: Direction,
+    // The turn number the unit was recruited on, if it was.
+    pub recruited_on: Option<i32>,
+}
+
+impl UnitState {
+    fn from_archetype(
+        id: UnitId,
+        faction: Faction,
+        hex: Hex,
+        archetype: &UnitArchetype,
+        center: Hex,
+    ) -> Self {
+        UnitState {
+            id,
+            faction,
+            hex,
+            health: archetype.health,
+            max_health: archetype.health,
+            move_range: archetype.move_range,
+            attack: archetype.attack,
+            defense: archetype.defense,
+            action_points: ActionPoints {
+                current: archetype.action_points,
+                max: archetype.action_points,
+            },
+            movement_spent: 0,
+            abilities: archetype.abilities.clone(),
+            cooldowns: AbilityCooldowns::default(),
+            status_effects: StatusEffects::default(),
+            skirmisher: archetype.traits.contains(&UnitTrait::Skirmisher),
+            facing: starting_facing(hex, center),
+            recruited_on: None,
+        }
+    }
+
+    pub fn stats(&self) -> EffectiveStats {
+        self.status_effects.apply(
+            &MoveRange(self.move_range),
+            &Attack(self.attack),
+            &Defense(self.defense),
+        )
+    }
+
+    // What's left of the unit's effective move range this turn.
+    pub fn movement_left(&self) -> u32 {
+        self.stats().move_range.saturating_sub(self.movement_spent)
+    }
+
+    fn spend_movement(&mut self, spent: u32) -> StateChange {
+        self.movement_spent = spent;
+        StateChange::MovementSpent {
+            unit: self.id,
+            spent,
+        }
+    }
+
+    fn can_use(&self, ability: &AbilityDef) -> Result<(), Rejection> {
+        let stats = self.stats();
+        if can_use_ability(ability, &stats, &self.action_points, &self.cooldowns) {
+            Ok(())
+        } else if !stats.can_act {
+            Err(Rejection::Stunned)
+        } else if self.action_points.current < ability.cost {
+            Err(Rejection::NotEnoughActionPoints {
+                needed: ability.cost,
+                left: self.action_points.current,
+            })
+        } else {
+            Err(Rejection::OnCooldown {
+                turns: self.cooldowns.remaining(&ability.name),
+            })
+        }
+    }
+
+    fn face(&mut self, facing: Direction) -> StateChange {
+        self.facing = facing;
+        StateChange::Facing {
+            unit: self.id,
+            facing: direction_index(facing),
+        }
+    }
+
+    fn cooldowns_change(&self) -> StateChange {
+        let mut cooldowns: Vec<(String, u32)> = self
+            .cooldowns
+            .0
+            .iter()
+            .map(|(name, turns)| (name.clone(), *turns))
+            .collect();
+        cooldowns.sort();
+        StateChange::Cooldowns {
+            unit: self.id,
+            cooldowns,
+        }
+    }
+}
+
+// A whole match as plain data, and the only place its rules are played. The
+// board shows the changes it makes, and the dedicated server runs matches on
+// it with nothing drawn.
+pub struct MatchState {
+    pub hex_map: HexMap,
+    pub elevation: Elevation,
+    pub terrain: TerrainMap,
+    pub structures: Structures,
+    pub turn_queue: TurnQueue,
+    pub treasury: Treasury,
+    // What can be recruited.
+    pub archetypes: UnitArchetypes,
+    // Id the next recruited unit gets.
+    pub next_unit_id: u32,
+    // Living units, in id order.
+    pub units: Vec<UnitState>,
+}
+
+impl MatchState {
+    // Sets the map up the way a new match starts on the board, giving units
+    // the same ids.
+    pub fn new(map: &MapData, archetypes: &UnitArchetypes, structure_defs: &StructureDefs) -> Self {
+        let factions: BTreeSet<u32> = map.units.iter().map(|unit| unit.faction).collect();
+        let hex_map = HexMap(map.all_hexes().into_iter().collect());
+        let center = hex_map.center();
+        let units: Vec<UnitState> = map
+            .units
+            .iter()
+            .filter_map(|map_unit| archetypes.0.get(&map_unit.archetype).map(|x| (map_unit, x)))
+            .enumerate()
+            .map(|(index, (map_unit, archetype))| {
+                UnitState::from_archetype(
+                    UnitId(index as u32),
+                    Faction(map_unit.faction),
+                    Hex::new(map_unit.hex.0, map_unit.hex.1),
+                    archetype,
+                    center,
+                )
+            })
+            .collect();
+        MatchState {
+            hex_map,
+            elevation: Elevation::from_map(map),
+            terrain: TerrainMap::from_map(map),
+            structures: Structures::from_map(map, structure_defs),
+            turn_queue: TurnQueue::new(factions.into_iter().map(Faction).collect()),
+            treasury: Treasury::from_map(map),
+            archetypes: archetypes.clone(),
+            next_unit_id: units.len() as u32,
+            units,
+        }
+    }
+
+    pub fn unit(&self, id: UnitId) -> Option<&UnitState> {
+        self.units.iter().find(|unit| unit.id == id)
+    }
+
+    fn unit_mut(&mut self, id: UnitId) -> Option<&mut UnitState> {
+        self.units.iter_mut().find(|unit| unit.id == id)
+    }
+
+    pub fn occupant(&self, hex: Hex) -> Option<&UnitState> {
+        self.units.iter().find(|unit| unit.hex == hex)
+    }
+
+    pub fn has_units(&self, faction: Faction) -> bool {
+        self.units.iter().any(|unit| unit.faction == faction)
+    }
+
+    pub fn zone_of_control(&self, unit: &UnitState) -> ZoneOfControl {
+        ZoneOfControl::against(
+            unit.faction,
+            unit.skirmisher,
+            self.units.iter().map(|other| (other.faction, other.hex)),
+        )
+    }
+
+    // Every hex `unit` can still move to this turn.
+    pub fn movement_range(&self, unit: &UnitState) -> HashSet<Hex> {
+        movement_range(
+            unit.hex,
+            unit.movement_left(),
+            &self.hex_map,
+            &self.elevation,
+            &self.zone_of_control(unit),
+        )
+    }
+
+    // The cheapest way for `unit` to get to `to`, however far that is.
+    fn path(&self, unit: &UnitState, to: Hex) -> Option<Vec<Hex>> {
+        find_path(
+            unit.hex,
+            to,
+            &self.hex_map,
+            &self.elevation,
+            &self.zone_of_control(unit),
+        )
+    }
+
+    // How well `unit` sees `target` for the ability.
+    fn sight(&self, unit: &UnitState, ability: &AbilityDef, target: Hex) -> Sight {
+        let units: Vec<Hex> = self.units.iter().map(|other| other.hex).collect();
+        ability.sight_to(unit.hex, target, &self.terrain, &self.elevation, &units)
+    }
+
+    // A unit `faction` may give orders to right now.
+    fn own_unit(&self, faction: Faction, id: UnitId) -> Result<&UnitState, Rejection> {
+        let unit = self.unit(id).ok_or(Rejection::UnknownUnit(id))?;
+        if unit.faction != faction {
+            return Err(Rejection::NotYourUnit);
+        }
+        if self.turn_queue.active_faction() != faction {
+            return Err(Rejection::NotYourTurn);
+        }
+        if unit.recruited_on == Some(self.turn_queue.turn_number) {
+            return Err(Rejection::JustRecruited);
+        }
+        Ok(unit)
+    }
+
+    // The first of the unit's abilities that can hurt `target` and is ready to
+    // use on it.
+    fn attack_with<'a>(
+        &self,
+        unit: &UnitState,
+        target: &UnitState,
+        ability_defs: &'a AbilityDefs,
+    ) -> Result<&'a AbilityDef, Rejection> {
+        let attacks: Vec<&AbilityDef> = unit
+            .abilities
+            .iter()
+            .filter_map(|name| ability_defs.0.get(name))
+            .filter(|ability| {
+                ability
+                    .effects
+                    .iter()
+                    .any(|effect| matches!(effect, AbilityEffect::Damage(_)))
+                    && ability.affects.includes(unit.faction, target.faction)
+                    && ability
+                        .shape
+                        .area(unit.hex, target.hex)
+                        .contains(&target.hex)
+            })
+            .collect();
+        if attacks.is_empty() {
+            return Err(Rejection::NoAttack);
+        }
+        let mut reason = None;
+        for ability in attacks.into_iter().filter(|ability| {
+            ability
+                .valid_targets(unit.hex, &self.hex_map, &self.structures)
+                .contains(&target.hex)
+        }) {
+            if self.sight(unit, ability, target.hex) == Sight::Blocked {
+                reason.get_or_insert(Rejection::NoLineOfSight);
+                continue;
+            }
+            match unit.can_use(ability) {
+                Ok(()) => return Ok(ability),
+                Err(err) => {
+                    reason.get_or_insert(err);
+                }
+            }
+        }
+        Err(reason.unwrap_or(Rejection::OutOfRange))
+    }
+
+    // Checks that `faction` is allowed to give `command`, and returns the
+    // command to play. Attacks are played as the ability they're made with.
+    pub fn validate(
+        &self,
+        faction: Faction,
+        command: &GameCommand,
+        ability_defs: &AbilityDefs,
+    ) -> Result<GameCommand, Rejection> {
+        match command {
+            GameCommand::SelectUnit(id) => {
+                let unit = self.unit(*id).ok_or(Rejection::UnknownUnit(*id))?;
+                if unit.faction != faction {
+                    return Err(Rejection::NotYourUnit);
+                }
+            }
+            GameCommand::Move { unit, to } => {
+                let unit = self.own_unit(faction, *unit)?;
+                let to = Hex::new(to.0, to.1);
+                if !self.hex_map.0.contains(&to) {
+                    return Err(Rejection::OffBoard);
+                }
+                if self.occupant(to).is_some_and(|other| other.id != unit.id) {
+                    return Err(Rejection::Occupied);
+                }
+                if !self.movement_range(unit).contains(&to) {
+                    let needed = self
+                        .path(unit, to)
+                        .map(|path| path_cost(&path, &self.elevation));
+                    return match needed {
+                        Some(needed) if needed <= unit.stats().move_range => {
+                            Err(Rejection::NotEnoughMovement {
+                                needed,
+                                left: unit.movement_left(),
+                            })
+                        }
+                        _ => Err(Rejection::OutOfRange),
+                    };
+                }
+            }
+            GameCommand::UseAbility {
+                unit,
+                ability,
+                target,
+            } => {
+                let unit = self.own_unit(faction, *unit)?;
+                let Some(def) = ability_defs
+                    .0
+                    .get(ability)
+                    .filter(|_| unit.abilities.contains(ability))
+                else {
+                    return Err(Rejection::UnknownAbility(ability.clone()));
+                };
+                unit.can_use(def)?;
+                let target = Hex::new(target.0, target.1);
+                if !def
+                    .valid_targets(unit.hex, &self.hex_map, &self.structures)
+                    .contains(&target)
+                {
+                    return Err(Rejection::OutOfRange);
+                }
+                if self.sight(unit, def, target) == Sight::Blocked {
+                    return Err(Rejection::NoLineOfSight);
+                }
+            }
+            GameCommand::Attack { unit, target } => {
+                let unit = self.own_unit(faction, *unit)?;
+                let target = self.unit(*target).ok_or(Rejection::UnknownUnit(*target))?;
+                if target.faction == unit.faction {
+                    return Err(Rejection::NotAnEnemy);
+                }
+                let ability = self.attack_with(unit, target, ability_defs)?;
+                return Ok(GameCommand::UseAbility {
+                    unit: unit.id,
+                    ability: ability.name.clone(),
+                    target: (target.hex.x, target.hex.y),
+                });
+            }
+            GameCommand::Wait(unit) => {
+                self.own_unit(faction, *unit)?;
+            }
+            GameCommand::Face { unit, towards } => {
+                let unit = self.own_unit(faction, *unit)?;
+                if !unit.stats().can_act {
+                    return Err(Rejection::Stunned);
+                }
+                if facing_towards(unit.hex, Hex::new(towards.0, towards.1)).is_none() {
+                    return Err(Rejection::OutOfRange);
+                }
+            }
+            GameCommand::Recruit { archetype, at } => {
+                if self.turn_queue.active_faction() != faction {
+                    return Err(Rejection::NotYourTurn);
+                }
+                let Some(archetype) = self.archetypes.0.get(archetype) else {
+                    return Err(Rejection::UnknownArchetype(archetype.clone()));
+                };
+                let at = Hex::new(at.0, at.1);
+                if !self.hex_map.0.contains(&at) {
+                    return Err(Rejection::OffBoard);
+                }
+                if self.occupant(at).is_some() {
+                    return Err(Rejection::Occupied);
+                }
+                if !self.structures.recruits_onto(at, faction) {
+                    return Err(Rejection::NoRecruiter);
+                }
+                let gold = self.treasury.gold(faction);
+                if gold < archetype.cost {
+                    return Err(Rejection::NotEnoughGold {
+                        needed: archetype.cost,
+                        left: gold,
+                    });
+                }
+            }
+            GameCommand::EndTurn => {
+                if self.turn_queue.active_faction() != faction {
+                    return Err(Rejection::NotYourTurn);
+                }
+            }
+        }
+        Ok(command.clone())
+    }
+
+    // Plays a command that has been validated, returning what changed.
+    pub fn apply(&mut self, command: &GameCommand, ability_defs: &AbilityDefs) -> Vec<StateChange> {
+        match command {
+            // Selection only matters to whoever is looking at the board.
+            GameCommand::SelectUnit(_) => Vec::new(),
+            GameCommand::Move { unit, to } => {
+                let Some(state) = self.unit(*unit) else {
+                    return Vec::new();
+                };
+                let to_hex = Hex::new(to.0, to.1);
+                let path = self.path(state, to_hex).unwrap_or_default();
+                // Units end up looking the way they took their last step.
+                let facing = match path.as_slice() {
+                    [.., last_but_one, last] => last_but_one.neighbor_direction(*last),
+                    _ => None,
+                };
+                // Ending up next to an enemy uses up the rest of the turn's
+                // movement.
+                let spent = if self.zone_of_control(state).stops(to_hex) {
+                    state.movement_spent.max(state.stats().move_range)
+                } else {
+                    state.movement_spent + path_cost(&path, &self.elevation)
+                };
+                let Some(state) = self.unit_mut(*unit) else {
+                    return Vec::new();
+                };
+                state.hex = to_hex;
+                let mut changes = vec![StateChange::UnitMoved {
+                    unit: *unit,
+                    to: *to,
+                }];
+                if let Some(facing) = facing {
+                    changes.push(state.face(facing));
+                }
+                changes.push(state.spend_movement(spent));
+                let faction = state.faction;
+                if self.structures.capture(to_hex, faction) {
+                    changes.push(StateChange::StructureCaptured {
+                        hex: *to,
+                        faction: faction.0,
+                    });
+                }
+                changes
+            }
+            GameCommand::UseAbility {
+                unit,
+                ability,
+                target,
+            } => self.use_ability(*unit, ability, Hex::new(target.0, target.1), ability_defs),
+            // Validating an attack turns it into the ability it's made with.
+            GameCommand::Attack { .. } => Vec::new(),
+            GameCommand::Wait(unit) => {
+                let Some(state) = self.unit_mut(*unit) else {
+                    return Vec::new();
+                };
+                state.action_points.current = 0;
+                vec![StateChange::ActionPoints {
+                    unit: *unit,
+                    current: 0,
+                }]
+            }
+            GameCommand::Face { unit, towards } => {
+                let Some(state) = self.unit_mut(*unit) else {
+                    return Vec::new();
+                };
+                match facing_towards(state.hex, Hex::new(towards.0, towards.1)) {
+                    Some(facing) => vec![state.face(facing)],
+                    None => Vec::new(),
+                }
+            }
+            GameCommand::Recruit { archetype, at } => self.recruit(archetype, Hex::new(at.0, at.1)),
+            GameCommand::EndTurn => self.end_turn(),
+        }
+    }
+
+    // Where a unit recruited at the structure on `hex` would be placed, if
+    // there's room next to it.
+    pub fn recruit_hex(&self, structure: Hex) -> Option<Hex> {
+        Direction::ALL_DIRECTIONS
+            .into_iter()
+            .map(|direction| structure.neighbor(direction))
+            .find(|hex| self.hex_map.0.contains(hex) && self.occupant(*hex).is_none())
+    }
+
+    fn recruit(&mut self, archetype: &str, at: Hex) -> Vec<StateChange> {
+        let Some(archetype) = self.archetypes.0.get(archetype) else {
+            return Vec::new();
+        };
+        let faction = self.turn_queue.active_faction();
+        let gold = self.treasury.gold(faction).saturating_sub(archetype.cost);
+        self.treasury.set(faction, gold);
+        let id = UnitId(self.next_unit_id);
+        self.next_unit_id += 1;
+        let mut unit = UnitState::from_archetype(id, faction, at, archetype, self.hex_map.center());
+        unit.recruited_on = Some(self.turn_queue.turn_number);
+        let name = archetype.name.clone();
+        // Ids only grow, so the units stay in id order.
+        self.units.push(unit);
+        vec![
+            StateChange::Gold {
+                faction: faction.0,
+                gold,
+            },
+            StateChange::UnitRecruited {
+                unit: id,
+                archetype: name,
+                faction: faction.0,
+                hex: (at.x, at.y),
+            },
+        ]
+    }
+
+    fn use_ability(
+        &mut self,
+        caster_id: UnitId,
+        ability: &str,
+        target: Hex,
+        ability_defs: &AbilityDefs,
+    ) -> Vec<StateChange> {
+        let (Some(def), Some(caster)) = (ability_defs.0.get(ability), self.unit_mut(caster_id))
+        else {
+            return Vec::new();
+        };
+        caster.action_points.current -= def.cost;
+        caster.cooldowns.0.insert(def.name.clone(), def.cooldown);
+        let mut changes = vec![
+            StateChange::ActionPoints {
+                unit: caster_id,
+                current: caster.action_points.current,
+            },
+            caster.cooldowns_change(),
+        ];
+        if let Some(facing) = facing_towards(caster.hex, target) {
+            changes.push(caster.face(facing));
+        }
+
+        let caster = caster.clone();
+        let caster_stats = caster.stats();
+        let area = def.shape.area(caster.hex, target);
+        let hexes: Vec<Hex> = self.units.iter().map(|unit| unit.hex).collect();
+        let targets: Vec<usize> = (0..self.units.len())
+            .filter(|&index| {
+                let unit = &self.units[index];
+                area.contains(&unit.hex) && def.affects.includes(caster.faction, unit.faction)
+            })
+            .collect();
+        // Every hit is worked out from the stats units had before the ability,
+        // and damage lands before healing.
+        let mut damage = Vec::new();
+        let mut healing = Vec::new();
+        let mut statuses = Vec::new();
+        for &index in targets.iter() {
+            let unit = &self.units[index];
+            for effect in def.effects.iter() {
+                match effect {
+                    AbilityEffect::Damage(amount) => {
+                        let downhill = self
+                            .elevation
+                            .level(caster.hex)
+                            .saturating_sub(self.elevation.level(unit.hex));
+                        let angle = attack_angle(unit.hex, unit.facing, caster.hex);
+                        let sight = def.sight_to(
+                            caster.hex,
+                            unit.hex,
+                            &self.terrain,
+                            &self.elevation,
+                            &hexes,
+                        );
+                        damage.push((
+                            index,
+                            ability_damage(
+                                *amount,
+                                &caster_stats,
+                                &unit.stats(),
+                                downhill,
+                                angle,
+                                sight,
+                            ),
+                        ));
+                    }
+                    AbilityEffect::Heal(amount) => healing.push((index, *amount)),
+                    AbilityEffect::Status(effect) => statuses.push((index, *effect)),
+                }
+            }
+        }
+        if let Some((index, _)) = damage.first() {
+            changes.push(StateChange::UnitAttacked {
+                attacker: caster_id,
+                target: self.units[*index].id,
+            });
+        }
+        for (index, amount) in damage {
+            let unit = &mut self.units[index];
+            if unit.health > 0 {
+                unit.health -= amount;
+            }
+        }
+        for (index, amount) in healing {
+            let unit = &mut self.units[index];
+            if unit.health > 0 {
+                unit.health = (unit.health + amount).min(unit.max_health);
+            }
+        }
+        for &(index, effect) in statuses.iter() {
+            self.units[index].status_effects.add(effect);
+        }
+        for index in targets {
+            let unit = &self.units[index];
+            changes.push(StateChange::Health {
+                unit: unit.id,
+                health: unit.health,
+            });
+            if statuses.iter().any(|(x, _)| *x == index) {
+                changes.push(StateChange::StatusEffects {
+                    unit: unit.id,
+                    effects: unit.status_effects.0.clone(),
+                });
+            }
+        }
+        self.units.retain(|unit| unit.health > 0);
+        changes
+    }
+
+    fn end_turn(&mut self) -> Vec<StateChange> {
+        let units = &self.units;
+        let started = self
+            .turn_queue
+            .pass(|faction| units.iter().any(|unit| unit.faction == faction));
+        let mut changes = vec![StateChange::TurnPassed {
+            turn_number: self.turn_queue.turn_number,
+            faction: self.turn_queue.active_faction().0,
+        }];
+        if started.is_none() {
+            return changes;
+        }
+        for &faction in self.turn_queue.factions.iter() {
+            let income = self.structures.income(faction);
+            if income > 0 {
+                let gold = self.treasury.gold(faction) + income;
+                self.treasury.set(faction, gold);
+                changes.push(StateChange::Gold {
+                    faction: faction.0,
+                    gold,
+                });
+            }
+        }
+        for unit in self.units.iter_mut() {
+            if !unit.status_effects.0.is_empty() {
+                let poison = unit.status_effects.tick();
+                if poison > 0 {
+                    unit.health -= poison;
+                    changes.push(StateChange::Health {
+                        unit: unit.id,
+                        health: unit.health,
+                    });
+                }
+                changes.push(StateChange::StatusEffects {
+                    unit: unit.id,
+                    effects: unit.status_effects.0.clone(),
+                });
+            }
+            let healing = self.structures.healing(unit.hex, unit.faction);
+            if healing > 0 && unit.health > 0 && unit.health < unit.max_health {
+                unit.health = (unit.health + healing).min(unit.max_health);
+                changes.push(StateChange::Health {
+                    unit: unit.id,
+                    health: unit.health,
+                });
+            }
+            unit.action_points.current = unit.action_points.max;
+            unit.cooldowns.tick();
+            changes.push(StateChange::ActionPoints {
+                unit: unit.id,
+                current: unit.action_points.current,
+            });
+            if unit.movement_spent > 0 {
+                changes.push(unit.spend_movement(0));
+            }
+            changes.push(unit.cooldowns_change());
+        }
+        self.units.retain(|unit| unit.health > 0);
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use hexx::shapes;
+
+    use super::*;
+    use crate::{
+        map::{MapStructure, MapUnit, Terrain},
+        status_effects::{StatusEffect, StatusKind},
+    };
+
+    const US: Faction = Faction(0);
+    const THEM: Faction = Faction(1);
+    // Ours, next to one of theirs.
+    const FRONT: UnitId = UnitId(0);
+    const ENEMY: UnitId = UnitId(1);
+    // Ours, away from the fighting.
+    const REAR: UnitId = UnitId(2);
+
+    // Two of our units and one of theirs, with a stone in front of ours and a
+    // keep of ours to recruit at. Our turn, with gold for one recruit.
+    fn match_state() -> (MatchState, AbilityDefs) {
+        let unit = |hex, faction| MapUnit {
+            archetype: String::from("Tidehunter"),
+            hex,
+            faction,
+        };
+        let map = MapData {
+            radius: Some(6),
+            terrain: BTreeMap::from([((0, -1), Terrain::Stone)]),
+            units: vec![unit((0, 0), 0), unit((1, 0), 1), unit((-2, 0), 0)],
+            structures: vec![MapStructure {
+                kind: String::from("Keep"),
+                hex: (-3, 3),
+                owner: Some(0),
+            }],
+            starting_gold: 8,
+            ..Default::default()
+        };
+        let state = MatchState::new(&map, &UnitArchetypes::load(), &StructureDefs::load());
+        (state, AbilityDefs::load())
+    }
+
+    fn rejection(
+        state: &MatchState,
+        ability_defs: &AbilityDefs,
+        command: GameCommand,
+    ) -> Rejection {
+        state
+            .validate(US, &command, ability_defs)
+            .expect_err("the command should be rejected")
+    }
+
+    fn use_ability(ability: &str, target: (i32, i32)) -> GameCommand {
+        GameCommand::UseAbility {
+            unit: FRONT,
+            ability: String::from(ability),
+            target,
+        }
+    }
+
+    fn recruit(archetype: &str, at: (i32, i32)) -> GameCommand {
+        GameCommand::Recruit {
+            archetype: String::from(archetype),
+            at,
+        }
+    }
+
+    #[test]
+    fn valid_commands_go_through() {
+        let (state, ability_defs) = match_state();
+        for command in [
+            GameCommand::Move {
+                unit: REAR,
+                to: (-2, -2),
+            },
+            use_ability("Strike", (1, 0)),
+            recruit("Tidehunter", (-2, 3)),
+            GameCommand::EndTurn,
+        ] {
+            assert_eq!(state.validate(US, &command, &ability_defs), Ok(command));
+        }
+    }
+
+    #[test]
+    fn commands_are_checked_against_the_state_earlier_ones_leave() {
+        let (mut state, ability_defs) = match_state();
+        let first = recruit("Tidehunter", (-2, 3));
+        let second = recruit("Tidehunter", (-3, 4));
+        assert!(state.validate(US, &second, &ability_defs).is_ok());
+        state.apply(&first, &ability_defs);
+        assert_eq!(
+            rejection(&state, &ability_defs, second),
+            Rejection::NotEnoughGold { needed: 8, left: 0 }
+        );
+    }
+
+    #[test]
+    fn unknown_unit() {
+        let (state, ability_defs) = match_state();
+        assert_eq!(
+            rejection(&state, &ability_defs, GameCommand::Wait(UnitId(9))),
+            Rejection::UnknownUnit(UnitId(9))
+        );
+    }
+
+    #[test]
+    fn not_your_turn() {
+        let (state, ability_defs) = match_state();
+        assert_eq!(
+            state.validate(THEM, &GameCommand::Wait(ENEMY), &ability_defs),
+            Err(Rejection::NotYourTurn)
+        );
+        assert_eq!(
+            state.validate(THEM, &GameCommand::EndTurn, &ability_defs),
+            Err(Rejection::NotYourTurn)
+        );
+    }
+
+    #[test]
+    fn not_your_unit() {
+        let (state, ability_defs) = match_state();
+        assert_eq!(
+            rejection(&state, &ability_defs, GameCommand::Wait(ENEMY)),
+            Rejection::NotYourUnit
+        );
+    }
+
+    #[test]
+    fn not_an_enemy() {
+        let (state, ability_defs) = match_state();
+        let command = GameCommand::Attack {
+            unit: FRONT,
+            target: REAR,
+        };
+        assert_eq!(
+            rejection(&state, &ability_defs, command),
+            Rejection::NotAnEnemy
+        );
+    }
+
+    #[test]
+    fn off_board() {
+        let (state, ability_defs) = match_state();
+        let command = GameCommand::Move {
+            unit: REAR,
+            to: (-9, 0),
+        };
+        assert_eq!(
+            rejection(&state, &ability_defs, command),
+            Rejection::OffBoard
+        );
+    }
+
+    #[test]
+    fn out_of_range() {
+        let (state, ability_defs) = match_state();
+        let command = GameCommand::Move {
+            unit: REAR,
+            to: (-2, 5),
+        };
+        assert_eq!(
+            rejection(&state, &ability_defs, command),
+            Rejection::OutOfRange
+        );
+        assert_eq!(
+            rejection(&state, &ability_defs, use_ability("Strike", (3, 0))),
+            Rejection::OutOfRange
+        );
+    }
+
+    #[test]
+    fn no_line_of_sight() {
+        let (state, ability_defs) = match_state();
+        assert_eq!(
+            rejection(&state, &ability_defs, use_ability("Gush", (0, -2))),
+            Rejection::NoLineOfSight
+        );
+    }
+
+    #[test]
+    fn occupied() {
+        let (state, ability_defs) = match_state();
+        let command = GameCommand::Move {
+            unit: REAR,
+            to: (0, 0),
+        };
+        assert_eq!(
+            rejection(&state, &ability_defs, command),
+            Rejection::Occupied
+        );
+    }
+
+    #[test]
+    fn stunned() {
+        let (mut state, ability_defs) = match_state();
+        state.units[0].status_effects.add(StatusEffect {
+            kind: StatusKind::Stun,
+            turns: 1,
+            potency: 0,
+        });
+        let command = GameCommand::Face {
+            unit: FRONT,
+            towards: (1, -1),
+        };
+        assert_eq!(
+            rejection(&state, &ability_defs, command),
+            Rejection::Stunned
+        );
+    }
+
+    #[test]
+    fn unknown_ability() {
+        let (state, ability_defs) = match_state();
+        assert_eq!(
+            rejection(&state, &ability_defs, use_ability("Fireball", (1, 0))),
+            Rejection::UnknownAbility(String::from("Fireball"))
+        );
+    }
+
+    #[test]
+    fn no_attack() {
+        let (mut state, ability_defs) = match_state();
+        state.units[0].abilities = vec![String::from("Heal")];
+        let command = GameCommand::Attack {
+            unit: FRONT,
+            target: ENEMY,
+        };
+        assert_eq!(
+            rejection(&state, &ability_defs, command),
+            Rejection::NoAttack
+        );
+    }
+
+    #[test]
+    fn not_enough_action_points() {
+        let (mut state, ability_defs) = match_state();
+        state.units[0].action_points.current = 0;
+        assert_eq!(
+            rejection(&state, &ability_defs, use_ability("Strike", (1, 0))),
+            Rejection::NotEnoughActionPoints { needed: 1, left: 0 }
+        );
+    }
+
+    #[test]
+    fn moves_share_the_turns_movement() {
+        let (mut state, ability_defs) = match_state();
+        let first = GameCommand::Move {
+            unit: REAR,
+            to: (-3, 0),
+        };
+        let second = GameCommand::Move {
+            unit: REAR,
+            to: (1, -4),
+        };
+        assert!(state.validate(US, &second, &ability_defs).is_ok());
+        state.apply(&first, &ability_defs);
+        assert_eq!(
+            rejection(&state, &ability_defs, second),
+            Rejection::NotEnoughMovement { needed: 4, left: 3 }
+        );
+        state.apply(&GameCommand::EndTurn, &ability_defs);
+        state.apply(&GameCommand::EndTurn, &ability_defs);
+        assert_eq!(state.unit(REAR).map(UnitState::movement_left), Some(4));
+    }
+
+    #[test]
+    fn ending_in_a_zone_of_control_uses_up_the_turns_movement() {
+        let (mut state, ability_defs) = match_state();
+        let next_to_them = GameCommand::Move {
+            unit: REAR,
+            to: (0, 1),
+        };
+        assert!(state.validate(US, &next_to_them, &ability_defs).is_ok());
+        let changes = state.apply(&next_to_them, &ability_defs);
+        assert!(changes.contains(&StateChange::MovementSpent {
+            unit: REAR,
+            spent: 4
+        }));
+        let step_back = GameCommand::Move {
+            unit: REAR,
+            to: (-1, 1),
+        };
+        assert_eq!(
+            rejection(&state, &ability_defs, step_back),
+            Rejection::NotEnoughMovement { needed: 1, left: 0 }
+        );
+    }
+
+    #[test]
+    fn on_cooldown() {
+        let (mut state, ability_defs) = match_state();
+        state.units[0].cooldowns.0.insert(String::from("Strike"), 2);
+        assert_eq!(
+            rejection(&state, &ability_defs, use_ability("Strike", (1, 0))),
+            Rejection::OnCooldown { turns: 2 }
+        );
+    }
+
+    #[test]
+    fn unknown_archetype() {
+        let (state, ability_defs) = match_state();
+        assert_eq!(
+            rejection(&state, &ability_defs, recruit("Kraken", (-2, 3))),
+            Rejection::UnknownArchetype(String::from("Kraken"))
+        );
+    }
+
+    #[test]
+    fn no_recruiter() {
+        let (state, ability_defs) = match_state();
+        assert_eq!(
+            rejection(&state, &ability_defs, recruit("Tidehunter", (2, 2))),
+            Rejection::NoRecruiter
+        );
+    }
+
+    #[test]
+    fn not_enough_gold() {
+        let (mut state, ability_defs) = match_state();
+        state.treasury.set(US, 3);
+        assert_eq!(
+            rejection(&state, &ability_defs, recruit("Tidehunter", (-2, 3))),
+            Rejection::NotEnoughGold { needed: 8, left: 3 }
+        );
+    }
+
+    #[test]
+    fn just_recruited() {
+        let (mut state, ability_defs) = match_state();
+        state.units[2].recruited_on = Some(state.turn_queue.turn_number);
+        assert_eq!(
+            rejection(&state, &ability_defs, GameCommand::Wait(REAR)),
+            Rejection::JustRecruited
+        );
+    }
+
+    #[test]
+    fn units_start_facing_the_middle_of_the_board() {
+        let middle = Hex::new(10, -4);
+        let map = MapData {
+            hexes: shapes::hexagon(middle, 3)
+                .map(|hex| (hex.x, hex.y))
+                .collect(),
+            units: vec![MapUnit {
+                archetype: String::from("Tidehunter"),
+                hex: (7, -4),
+                faction: 0,
+            }],
+            ..Default::default()
+        };
+        let state = MatchState::new(&map, &UnitArchetypes::load(), &StructureDefs::load());
+        assert_eq!(state.hex_map.center(), middle);
+        assert_eq!(
+            Some(state.units[0].facing),
+            facing_towards(Hex::new(7, -4), middle)
+        );
+    }
+}
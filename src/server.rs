@@ -0,0 +1,439 @@
+use std::{
+    collections::VecDeque,
+    net::{SocketAddr, TcpListener},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    abilities::AbilityDefs,
+    archetypes::UnitArchetypes,
+    components::Faction,
+    game_command::GameCommand,
+    map::MapData,
+    network::{start_connection, NetEvent, NetMessage},
+    rules::MatchState,
+    startup::new_seed,
+    structures::StructureDefs,
+};
+
+// What the dedicated server was started with.
+#[derive(Resource, Clone, Debug)]
+pub struct ServerConfig {
+    pub address: String,
+    pub map: String,
+    // Players that have to join before the match starts. Factions nobody
+    // plays pass their turns.
+    pub players: usize,
+}
+
+struct Client {
+    events: Mutex<Receiver<NetEvent>>,
+    outgoing: Option<Sender<NetMessage>>,
+    faction: Option<Faction>,
+}
+
+impl Client {
+    fn send(&mut self, message: NetMessage) {
+        if let Some(outgoing) = &self.outgoing {
+            if outgoing.send(message).is_err() {
+                self.outgoing = None;
+            }
+        }
+    }
+}
+
+// Runs a match for the players connected to it. Every command is checked
+// against the rules before it's played, and what changed is sent to everyone.
+#[derive(Resource)]
+pub struct Server {
+    // Where players connect. Binding port 0 picks a free one.
+    pub address: SocketAddr,
+    new_clients: Mutex<Receiver<Receiver<NetEvent>>>,
+    clients: Vec<Client>,
+    map: MapData,
+    state: Option<MatchState>,
+    // Commands waiting to be played, with the index of the client that sent
+    // them.
+    commands: VecDeque<(usize, GameCommand)>,
+}
+
+impl Server {
+    fn broadcast(&mut self, message: NetMessage) {
+        for client in self.clients.iter_mut() {
+            client.send(message.clone());
+        }
+    }
+
+    fn played(&self, faction: Faction) -> bool {
+        self.clients
+            .iter()
+            .any(|client| client.faction == Some(faction) && client.outgoing.is_some())
+    }
+}
+
+pub struct ServerPlugin;
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(UnitArchetypes::load())
+            .insert_resource(AbilityDefs::load())
+            .insert_resource(StructureDefs::load())
+            .add_systems(Startup, start_server)
+            .add_systems(
+                Update,
+                (accept_clients, poll_clients, start_match, play_commands)
+                    .chain()
+                    .run_if(resource_exists::<Server>()),
+            );
+    }
+}
+
+fn start_server(mut commands: Commands, config: Res<ServerConfig>) {
+    let bound = TcpListener::bind(&config.address)
+        .and_then(|listener| listener.local_addr().map(|address| (listener, address)));
+    let (listener, address) = match bound {
+        Ok(bound) => bound,
+        Err(err) => {
+            error!("Could not listen on {}: {}", config.address, err);
+            return;
+        }
+    };
+    let (new_clients, new_clients_rx) = channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let (events, events_rx) = channel();
+            start_connection(stream, events);
+            if new_clients.send(events_rx).is_err() {
+                return;
+            }
+        }
+    });
+    info!(
+        "Listening on {}, waiting for {} players",
+        address, config.players
+    );
+    commands.insert_resource(Server {
+        address,
+        new_clients: Mutex::new(new_clients_rx),
+        clients: Vec::new(),
+        map: MapData::load(&config.map),
+        state: None,
+        commands: VecDeque::new(),
+    });
+}
+
+// Players can only join before the match starts.
+fn accept_clients(mut server: ResMut<Server>) {
+    let new_clients: Vec<Receiver<NetEvent>> =
+        server.new_clients.lock().unwrap().try_iter().collect();
+    for events in new_clients {
+        if server.state.is_some() {
+            info!("Turned a player away, the match has started");
+            continue;
+        }
+        server.clients.push(Client {
+            events: Mutex::new(events),
+            outgoing: None,
+            faction: None,
+        });
+    }
+}
+
+// A player that leaves mid-match has their turns passed from then on.
+fn poll_clients(mut server: ResMut<Server>, ability_defs: Res<AbilityDefs>) {
+    let server = server.as_mut();
+    let mut left = false;
+    for (index, client) in server.clients.iter_mut().enumerate() {
+        let events: Vec<NetEvent> = client.events.lock().unwrap().try_iter().collect();
+        for event in events {
+            match event {
+                NetEvent::Connected(outgoing) => {
+                    info!("Player {} joined", index + 1);
+                    client.outgoing = Some(outgoing);
+                }
+                // A player only ever plays the faction it was given, whatever
+                // the command says.
+                NetEvent::Received(NetMessage::Command { command, .. }) => {
+                    server.commands.push_back((index, command));
+                }
+                // Nothing else is expected from a player.
+                NetEvent::Received(_) => (),
+                NetEvent::Disconnected(reason) => {
+                    info!("Player {} left: {}", index + 1, reason);
+                    client.outgoing = None;
+                    left = true;
+                }
+            }
+        }
+    }
+    let connected = server
+        .clients
+        .iter()
+        .filter(|client| client.outgoing.is_some())
+        .count();
+    if server.state.is_some() && connected == 0 {
+        info!("Everyone left, waiting for players");
+        server.state = None;
+        server.clients.clear();
+        server.commands.clear();
+    }
+    if left && server.state.is_some() {
+        pass_unplayed_turns(server, &ability_defs);
+    }
+}
+
+// Starts once enough players have joined, giving each a faction in turn order.
+fn start_match(
+    mut server: ResMut<Server>,
+    config: Res<ServerConfig>,
+    archetypes: Res<UnitArchetypes>,
+    ability_defs: Res<AbilityDefs>,
+    structure_defs: Res<StructureDefs>,
+) {
+    let server = server.as_mut();
+    if server.state.is_some() {
+        return;
+    }
+    server.clients.retain(|client| client.outgoing.is_some());
+    if server.clients.len() < config.players.max(1) {
+        return;
+    }
+    let state = MatchState::new(&server.map, &archetypes, &structure_defs);
+    let seed = new_seed();
+    let map = server.map.clone();
+    let factions = state.turn_queue.factions.clone();
+    for (client, faction) in server.clients.iter_mut().zip(factions) {
+        client.faction = Some(faction);
+        client.send(NetMessage::Start {
+            seed,
+            map: Box::new(map.clone()),
+            faction: faction.0,
+        });
+    }
+    info!(
+        "Started a match on {} with {} players",
+        map.name,
+        server.clients.len()
+    );
+    server.state = Some(state);
+    server.commands.clear();
+    pass_unplayed_turns(server, &ability_defs);
+}
+
+fn play_commands(mut server: ResMut<Server>, ability_defs: Res<AbilityDefs>) {
+    let server = server.as_mut();
+    while let Some((index, command)) = server.commands.pop_front() {
+        let Some(faction) = server.clients.get(index).and_then(|client| client.faction) else {
+            continue;
+        };
+        let Some(state) = server.state.as_mut() else {
+            return;
+        };
+        let command = match state.validate(faction, &command, &ability_defs) {
+            Ok(command) => command,
+            Err(reason) => {
+                server.clients[index].send(NetMessage::Rejected { command, reason });
+                continue;
+            }
+        };
+        let changes = state.apply(&command, &ability_defs);
+        if !changes.is_empty() {
+            server.broadcast(NetMessage::Changes(changes));
+        }
+        if let GameCommand::EndTurn = command {
+            pass_unplayed_turns(server, &ability_defs);
+        }
+    }
+}
+
+// There's no AI on the server yet, so factions nobody plays pass straight away,
+// and so do factions that have been wiped out.
+fn pass_unplayed_turns(server: &mut Server, ability_defs: &AbilityDefs) {
+    let Some(factions) = server
+        .state
+        .as_ref()
+        .map(|state| state.turn_queue.factions.len())
+    else {
+        return;
+    };
+    for _ in 0..factions {
+        let Some(state) = server.state.as_ref() else {
+            return;
+        };
+        let active = state.turn_queue.active_faction();
+        if state.has_units(active) && server.played(active) {
+            return;
+        }
+        let Some(state) = server.state.as_mut() else {
+            return;
+        };
+        let changes = state.apply(&GameCommand::EndTurn, ability_defs);
+        server.broadcast(NetMessage::Changes(changes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Shutdown, TcpStream},
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::{
+        components::UnitId,
+        map::DEFAULT_MAP,
+        rules::{Rejection, StateChange},
+    };
+
+    fn server(players: usize) -> App {
+        let mut app = App::new();
+        app.insert_resource(ServerConfig {
+            address: String::from("127.0.0.1:0"),
+            map: String::from(DEFAULT_MAP),
+            players,
+        })
+        .add_plugins(ServerPlugin);
+        app.update();
+        app
+    }
+
+    // Connects a player, keeping hold of the socket so the test can hang up.
+    fn join(app: &App) -> (Sender<NetMessage>, Receiver<NetEvent>, TcpStream) {
+        let address = app.world.resource::<Server>().address;
+        let stream = TcpStream::connect(address).unwrap();
+        let socket = stream.try_clone().unwrap();
+        let (events, events_rx) = channel();
+        start_connection(stream, events);
+        let Ok(NetEvent::Connected(outgoing)) = events_rx.recv() else {
+            panic!("not connected");
+        };
+        (outgoing, events_rx, socket)
+    }
+
+    // A server waiting for one player, and that player's connection to it.
+    fn server_with_player() -> (App, Sender<NetMessage>, Receiver<NetEvent>) {
+        let app = server(1);
+        let (outgoing, events, _) = join(&app);
+        (app, outgoing, events)
+    }
+
+    // Runs the server until the player gets a message.
+    fn next_message(app: &mut App, events: &Receiver<NetEvent>) -> NetMessage {
+        let start = Instant::now();
+        loop {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            app.update();
+            if let Ok(NetEvent::Received(message)) = events.try_recv() {
+                return message;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn started(app: &mut App, events: &Receiver<NetEvent>) -> Faction {
+        let NetMessage::Start { faction, .. } = next_message(app, events) else {
+            panic!("expected the match to start");
+        };
+        Faction(faction)
+    }
+
+    #[test]
+    fn invalid_commands_are_sent_back() {
+        let (mut app, outgoing, events) = server_with_player();
+        let faction = started(&mut app, &events);
+        let command = GameCommand::Wait(UnitId(999));
+        outgoing
+            .send(NetMessage::Command {
+                faction: faction.0,
+                command: command.clone(),
+            })
+            .unwrap();
+        let NetMessage::Rejected {
+            command: rejected,
+            reason,
+        } = next_message(&mut app, &events)
+        else {
+            panic!("expected the command to be rejected");
+        };
+        assert_eq!(rejected, command);
+        assert_eq!(reason, Rejection::UnknownUnit(UnitId(999)));
+    }
+
+    #[test]
+    fn valid_commands_are_played_and_broadcast() {
+        let (mut app, outgoing, events) = server_with_player();
+        let faction = started(&mut app, &events);
+        outgoing
+            .send(NetMessage::Command {
+                faction: faction.0,
+                command: GameCommand::EndTurn,
+            })
+            .unwrap();
+        let NetMessage::Changes(changes) = next_message(&mut app, &events) else {
+            panic!("expected the changes to be broadcast");
+        };
+        assert!(changes
+            .iter()
+            .any(|change| matches!(change, StateChange::TurnPassed { .. })));
+        // Nobody plays the other faction, so its turn passes straight back.
+        let NetMessage::Changes(_) = next_message(&mut app, &events) else {
+            panic!("expected the unplayed turn to pass");
+        };
+        let state = app.world.resource::<Server>().state.as_ref().unwrap();
+        assert_eq!(state.turn_queue.active_faction(), faction);
+    }
+
+    #[test]
+    fn wiped_out_factions_are_passed() {
+        let (mut app, _outgoing, events) = server_with_player();
+        let faction = started(&mut app, &events);
+        let mut server = app.world.resource_mut::<Server>();
+        let state = server.state.as_mut().unwrap();
+        state.units.retain(|unit| unit.faction != faction);
+        let ability_defs = AbilityDefs::load();
+        pass_unplayed_turns(&mut server, &ability_defs);
+        let state = server.state.as_ref().unwrap();
+        assert_ne!(state.turn_queue.active_faction(), faction);
+    }
+
+    #[test]
+    fn leaving_on_your_turn_passes_it() {
+        let mut app = server(2);
+        let first = join(&app);
+        let second = join(&app);
+        let first_faction = started(&mut app, &first.1);
+        let second_faction = started(&mut app, &second.1);
+        let active = app
+            .world
+            .resource::<Server>()
+            .state
+            .as_ref()
+            .unwrap()
+            .turn_queue
+            .active_faction();
+        let (leaving, staying, staying_faction) = if active == first_faction {
+            (first, second, second_faction)
+        } else {
+            (second, first, first_faction)
+        };
+        leaving.2.shutdown(Shutdown::Both).unwrap();
+        let NetMessage::Changes(changes) = next_message(&mut app, &staying.1) else {
+            panic!("expected the turn to pass");
+        };
+        assert!(changes
+            .iter()
+            .any(|change| matches!(change, StateChange::TurnPassed { .. })));
+        let state = app.world.resource::<Server>().state.as_ref().unwrap();
+        assert_eq!(state.turn_queue.active_faction(), staying_faction);
+    }
+}
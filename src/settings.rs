@@ -0,0 +1,234 @@
+use std::fs;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Player settings are kept in the working directory rather than in `assets`,
+// since they're written back whenever they change.
+const SETTINGS_PATH: &str = "settings.ron";
+
+// How much each press of a volume button changes the volume by.
+const VOLUME_STEP: f32 = 0.1;
+
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct VolumeSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for VolumeSettings {
+    fn default() -> Self {
+        VolumeSettings {
+            master: 1.0,
+            music: 0.6,
+            sfx: 0.8,
+        }
+    }
+}
+
+impl VolumeSettings {
+    fn channel(&self, channel: VolumeChannel) -> f32 {
+        match channel {
+            VolumeChannel::Master => self.master,
+            VolumeChannel::Music => self.music,
+            VolumeChannel::Sfx => self.sfx,
+        }
+    }
+
+    fn channel_mut(&mut self, channel: VolumeChannel) -> &mut f32 {
+        match channel {
+            VolumeChannel::Master => &mut self.master,
+            VolumeChannel::Music => &mut self.music,
+            VolumeChannel::Sfx => &mut self.sfx,
+        }
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.master * self.music
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.master * self.sfx
+    }
+}
+
+#[derive(Resource, Reflect, Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct Settings {
+    pub volume: VolumeSettings,
+}
+
+impl Settings {
+    fn load() -> Settings {
+        fs::read_to_string(SETTINGS_PATH)
+            .ok()
+            .and_then(|contents| ron::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VolumeChannel {
+    Master,
+    Music,
+    Sfx,
+}
+
+impl VolumeChannel {
+    const ALL: [VolumeChannel; 3] = [
+        VolumeChannel::Master,
+        VolumeChannel::Music,
+        VolumeChannel::Sfx,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            VolumeChannel::Master => "Master",
+            VolumeChannel::Music => "Music",
+            VolumeChannel::Sfx => "Effects",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct VolumeText(pub VolumeChannel);
+
+// Turns a volume down (negative) or up (positive) by one step.
+#[derive(Component)]
+pub struct VolumeButton {
+    pub channel: VolumeChannel,
+    pub step: f32,
+}
+
+fn save_settings(settings: Res<Settings>) {
+    let contents = match ron::ser::to_string_pretty(&*settings, default()) {
+        Ok(contents) => contents,
+        Err(err) => {
+            error!("Could not serialize settings: {}", err);
+            return;
+        }
+    };
+    if let Err(err) = fs::write(SETTINGS_PATH, contents) {
+        error!("Could not save settings to {}: {}", SETTINGS_PATH, err);
+    }
+}
+
+fn spawn_volume_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    // Above the ability bar.
+                    bottom: Val::Px(40.0),
+                    left: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    row_gap: Val::Px(2.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                ..default()
+            },
+            Name::new("Volume Panel"),
+        ))
+        .with_children(|parent| {
+            for channel in VolumeChannel::ALL {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(4.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for (label, step) in [("-", -VOLUME_STEP), ("+", VOLUME_STEP)] {
+                            parent
+                                .spawn((
+                                    ButtonBundle {
+                                        style: Style {
+                                            width: Val::Px(24.0),
+                                            justify_content: JustifyContent::Center,
+                                            ..default()
+                                        },
+                                        background_color: Color::rgb(0.15, 0.15, 0.15).into(),
+                                        ..default()
+                                    },
+                                    VolumeButton { channel, step },
+                                ))
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(
+                                        label,
+                                        TextStyle {
+                                            font_size: 16.0,
+                                            ..default()
+                                        },
+                                    ));
+                                });
+                        }
+                        parent.spawn((
+                            TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font_size: 16.0,
+                                    ..default()
+                                },
+                            ),
+                            VolumeText(channel),
+                        ));
+                    });
+            }
+        });
+}
+
+fn press_volume_buttons(
+    button_q: Query<(&Interaction, &VolumeButton), Changed<Interaction>>,
+    mut settings: ResMut<Settings>,
+) {
+    for (interaction, button) in button_q.iter() {
+        if *interaction == Interaction::Pressed {
+            let volume = settings.volume.channel_mut(button.channel);
+            // Rounded to a whole number of steps, so repeated steps don't drift.
+            let steps = ((*volume + button.step) / VOLUME_STEP).round();
+            *volume = (steps * VOLUME_STEP).clamp(0.0, 1.0);
+        }
+    }
+}
+
+fn update_volume_text(settings: Res<Settings>, mut text_q: Query<(&mut Text, &VolumeText)>) {
+    for (mut text, volume_text) in text_q.iter_mut() {
+        text.sections[0].value = format!(
+            "{} {:.0}%",
+            volume_text.0.label(),
+            settings.volume.channel(volume_text.0) * 100.0
+        );
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load())
+            .register_type::<Settings>()
+            .add_systems(Startup, spawn_volume_panel)
+            .add_systems(Update, press_volume_buttons)
+            .add_systems(
+                Update,
+                (
+                    update_volume_text.run_if(resource_changed::<Settings>()),
+                    // Settings are only written back once something changes
+                    // them, not when they're first loaded.
+                    save_settings
+                        .run_if(resource_changed::<Settings>())
+                        .run_if(not(resource_added::<Settings>())),
+                )
+                    .after(press_volume_buttons),
+            );
+    }
+}
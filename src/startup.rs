@@ -1,39 +1,134 @@
+use std::{
+    collections::BTreeSet,
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use crate::{
-    bundles::LayerBundle,
-    components::{BaseHex, BoardLoc, HexTile, Layer, MoveRange, Selectable},
-    constants::{CENTER_HEX, LAYOUT},
-    enums::MapLayer,
-    resources::HexMap,
-    AppState, Unit,
+    abilities::{Abilities, AbilityCooldowns},
+    animation::UnitAnimation,
+    archetypes::{Archetype, UnitArchetype, UnitArchetypes, UnitTrait},
+    components::{
+        ActionPoints, Attack, AttackRange, BaseHex, BaseLayer, BoardLoc, Defense, EffectiveStats,
+        Facing, Faction, HexTile, Layer, MapUnit, MoveRange, MovementSpent, Skirmisher, Unit,
+    },
+    constants::{CLIFF_TEXTURE, ELEVATION_STEP, TILE_Z, UNIT_Z},
+    depth::YSort,
+    events::{NewMatch, RebuildBoard},
+    facing::starting_facing,
+    helpers::board::Board,
+    map::{MapData, DEFAULT_MAP},
+    resources::{Elevation, HexMap, MapLayout, MatchSeed, NextUnitId, TerrainMap, TurnQueue},
+    states::AppState,
+    status_effects::StatusEffects,
 };
 use bevy::prelude::*;
-use hexx::{shapes, Hex};
+use hexx::Hex;
+
+const SEED_VAR: &str = "LICHDOM_SEED";
 
 pub struct StartupPlugin;
 
+// Builds the board for a new match. Anything that inspects the board should run
+// after it, so it never sees the board half built.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MatchSetup;
+
+// Copies the map's layout, hexes, elevation and terrain into the resources the
+// game reads them from.
+fn sync_map_resources(
+    map: Res<MapData>,
+    mut layout: ResMut<MapLayout>,
+    mut hex_map: ResMut<HexMap>,
+    mut elevation: ResMut<Elevation>,
+    mut terrain: ResMut<TerrainMap>,
+) {
+    *layout = MapLayout::from_config(&map.layout);
+    hex_map.0 = map.all_hexes().into_iter().collect();
+    *elevation = Elevation::from_map(&map);
+    *terrain = TerrainMap::from_map(&map);
+}
+
+fn reset_match(
+    mut ev_new_match: EventReader<NewMatch>,
+    map: Res<MapData>,
+    mut seed: ResMut<MatchSeed>,
+    mut turn_queue: ResMut<TurnQueue>,
+) {
+    if let Some(ev) = ev_new_match.iter().last() {
+        seed.0 = ev.seed;
+    }
+    // Every faction with units on the map takes a turn, lowest first.
+    let factions: BTreeSet<u32> = map.units.iter().map(|unit| unit.faction).collect();
+    *turn_queue = TurnQueue::new(factions.into_iter().map(Faction).collect());
+}
+
+fn despawn_board(
+    mut commands: Commands,
+    base_layer_q: Query<Entity, With<BaseLayer>>,
+    unit_q: Query<Entity, With<Archetype>>,
+    layer_q: Query<Entity, With<Layer>>,
+) {
+    for entity in base_layer_q.iter().chain(unit_q.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in layer_q.iter() {
+        commands.entity(entity).despawn_descendants();
+    }
+}
+
 fn generate_grid(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut hex_map: ResMut<HexMap>,
+    board: Board,
+    terrain: Res<TerrainMap>,
+    map: Res<MapData>,
 ) {
-    let texture_handle: Handle<Image> = asset_server.load(MapLayer::get_texture(MapLayer::BASE));
-    let entities: Vec<Entity> = shapes::hexagon(CENTER_HEX, 5)
+    let texture_handle: Handle<Image> = asset_server.load(board.layout.tile_texture.as_str());
+    let cliff_handle: Handle<Image> = asset_server.load(CLIFF_TEXTURE);
+    let tile_size = board.layout.tile_size;
+    // Each slice of cliff covers the lower half of the tile, dropped by one level.
+    let cliff_size = Vec2::new(tile_size.x, tile_size.y / 2.0 + ELEVATION_STEP);
+    let entities: Vec<Entity> = map
+        .all_hexes()
+        .into_iter()
         .map(|hex| {
-            hex_map.0.insert(hex);
-
-            let pos = LAYOUT.hex_to_world_pos(hex);
+            let pos = board.world_pos(hex);
             commands
                 .spawn((
                     Name::new(format!("{} {}", hex.x, hex.y)),
                     BaseHex,
                     HexTile(hex),
+                    YSort::new(TILE_Z),
                     SpriteBundle {
+                        sprite: Sprite {
+                            color: terrain.get(hex).color(),
+                            custom_size: Some(tile_size),
+                            ..default()
+                        },
                         texture: texture_handle.clone(),
-                        transform: Transform::from_xyz(pos.x, pos.y, 0.0),
+                        transform: Transform::from_xyz(pos.x, pos.y, TILE_Z),
                         ..default()
                     },
                 ))
                 .with_children(|b| {
+                    // One slice of cliff per level, stacked down to the ground
+                    // behind the tile.
+                    for step in 0..board.elevation.level(hex) {
+                        b.spawn(SpriteBundle {
+                            sprite: Sprite {
+                                custom_size: Some(cliff_size),
+                                ..default()
+                            },
+                            texture: cliff_handle.clone(),
+                            transform: Transform::from_xyz(
+                                0.0,
+                                -(step as f32) * ELEVATION_STEP - cliff_size.y / 2.0,
+                                -0.0005,
+                            ),
+                            ..default()
+                        });
+                    }
                     b.spawn(Text2dBundle {
                         text: Text::from_section(
                             format!("{},{}", hex.x, hex.y),
@@ -54,15 +149,8 @@ fn generate_grid(
     let parent_layer = commands
         .spawn((
             Name::new(String::from("BaseLayer")),
-            LayerBundle {
-                layer: Layer {
-                    layer_type: MapLayer::BASE,
-                },
-                global_transform: GlobalTransform::from_xyz(0.0, 0.0, 0.0),
-                transform: Transform::from_xyz(0.0, 0.0, 0.0),
-                visibility: Visibility::Visible,
-                computed_visibility: ComputedVisibility::default(),
-            },
+            BaseLayer,
+            SpatialBundle::default(),
         ))
         .id();
     commands.entity(parent_layer).push_children(&entities);
@@ -72,48 +160,144 @@ fn startup(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
 }
 
-fn spawn_layers(mut commands: Commands) {
-    for layer in MapLayer::iterator().skip(1) {
-        commands.spawn((
-            Name::new(String::from(layer.to_string())),
-            LayerBundle {
-                layer: Layer { layer_type: *layer },
-                global_transform: GlobalTransform::from_xyz(0.0, 0.0, 0.0),
-                transform: Transform::from_xyz(0.0, 0.0, 0.0),
-                visibility: Visibility::Visible,
-                computed_visibility: ComputedVisibility::default(),
+pub fn spawn_unit(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    archetype: &UnitArchetype,
+    hex: Hex,
+    faction: Faction,
+    board: &Board,
+) -> Entity {
+    let pos = board.world_pos(hex);
+    let mut sprite_sheet = archetype.sprite_sheet_bundle(
+        asset_server,
+        texture_atlases,
+        Transform::from_xyz(pos.x, pos.y, UNIT_Z),
+    );
+    sprite_sheet.sprite.color = faction.color();
+    // Grouped, as bundles only go up to 15 components.
+    let mut unit = commands.spawn((
+        (
+            sprite_sheet,
+            archetype.clips.clone(),
+            UnitAnimation::default(),
+            YSort::new(UNIT_Z),
+        ),
+        Archetype(archetype.name.clone()),
+        Unit {
+            health: archetype.health,
+            max_health: archetype.health,
+        },
+        (
+            MoveRange(archetype.move_range),
+            AttackRange(archetype.attack_range),
+            Attack(archetype.attack),
+            Defense(archetype.defense),
+            StatusEffects::default(),
+            EffectiveStats {
+                move_range: archetype.move_range,
+                attack: archetype.attack,
+                defense: archetype.defense,
+                can_act: true,
+            },
+        ),
+        (
+            ActionPoints {
+                current: archetype.action_points,
+                max: archetype.action_points,
             },
-        ));
+            MovementSpent::default(),
+            Abilities(archetype.abilities.clone()),
+            AbilityCooldowns::default(),
+        ),
+        faction,
+        BoardLoc { hex },
+        Facing(starting_facing(hex, board.hex_map.center())),
+        Name::new(archetype.name.clone()),
+    ));
+    if archetype.traits.contains(&UnitTrait::Skirmisher) {
+        unit.insert(Skirmisher);
     }
+    unit.id()
 }
 
-fn place_starting_unit(
+fn spawn_map_units(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut next_state: ResMut<NextState<AppState>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    archetypes: Res<UnitArchetypes>,
+    map: Res<MapData>,
+    mut next_unit_id: ResMut<NextUnitId>,
+    board: Board,
 ) {
-    let axial_pos = Hex { x: 1, y: 0 };
-    let pos = LAYOUT.hex_to_world_pos(axial_pos);
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite { ..default() },
-            texture: asset_server.load("tidehunter.png"),
-            transform: Transform::from_xyz(pos.x, pos.y, 10.0),
-            ..default()
-        },
-        Unit { health: 10 },
-        MoveRange(4),
-        Selectable,
-        BoardLoc { hex: axial_pos },
-        Name::new(String::from("Tidehunter")),
-    ));
+    // The map's units always get the first ids.
+    *next_unit_id = NextUnitId::default();
+    for (index, map_unit) in map.units.iter().enumerate() {
+        let Some(archetype) = archetypes.0.get(&map_unit.archetype) else {
+            error!("Unknown unit archetype {}", map_unit.archetype);
+            continue;
+        };
+        let unit = spawn_unit(
+            &mut commands,
+            &asset_server,
+            &mut texture_atlases,
+            archetype,
+            Hex::new(map_unit.hex.0, map_unit.hex.1),
+            Faction(map_unit.faction),
+            &board,
+        );
+        commands
+            .entity(unit)
+            .insert((next_unit_id.take(), MapUnit(index)));
+    }
+}
 
+// A different seed for every match, unless it's being replayed or pinned with
+// the `LICHDOM_SEED` environment variable.
+pub fn new_seed() -> u64 {
+    if let Some(seed) = env::var(SEED_VAR).ok().and_then(|x| x.parse().ok()) {
+        return seed;
+    }
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+fn start_match(
+    mut next_state: ResMut<NextState<AppState>>,
+    mut ev_new_match: EventWriter<NewMatch>,
+) {
+    ev_new_match.send(NewMatch { seed: new_seed() });
     next_state.set(AppState::InGame)
 }
 
 impl Plugin for StartupPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (startup, generate_grid, spawn_layers))
-            .add_systems(Startup, place_starting_unit);
+        let map = MapData::load(DEFAULT_MAP);
+        app.insert_resource(MapLayout::from_config(&map.layout))
+            .init_resource::<Elevation>()
+            .init_resource::<TerrainMap>()
+            .init_resource::<MatchSeed>()
+            .init_resource::<NextUnitId>()
+            .insert_resource(map)
+            .add_systems(Startup, (startup, start_match))
+            .add_systems(
+                Update,
+                (
+                    reset_match.run_if(on_event::<NewMatch>()),
+                    (
+                        despawn_board,
+                        sync_map_resources,
+                        generate_grid,
+                        spawn_map_units,
+                    )
+                        .chain()
+                        .run_if(on_event::<NewMatch>().or_else(on_event::<RebuildBoard>())),
+                )
+                    .chain()
+                    .in_set(MatchSetup),
+            );
     }
 }
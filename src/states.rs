@@ -5,6 +5,10 @@ pub enum AppState {
     #[default]
     LoadingMap,
     InGame,
+    GameOver,
+    Editor,
+    // Hosting or joining a network match.
+    Lobby,
 }
 
 #[derive(States, PartialEq, Eq, Debug, Clone, Hash, Default)]
@@ -13,4 +17,11 @@ pub enum PlayerState {
     Idle,
     UnitSelected,
     UnitMoving,
+    Targeting,
+    // Picking one of the actions offered for a hex.
+    ContextMenu,
+    // Picking a unit to recruit.
+    Recruiting,
+    // The board is hidden while the next hot-seat player takes the device.
+    PassingDevice,
 }
@@ -0,0 +1,183 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Attack, Defense, EffectiveStats, MoveRange};
+
+const ICON_SIZE: f32 = 8.0;
+
+#[derive(Serialize, Deserialize, Reflect, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StatusKind {
+    // Deals `potency` damage at the start of every turn.
+    Poison,
+    // Can't move or use abilities.
+    Stun,
+    // Moves `potency` fewer hexes.
+    Slow,
+    // Adds `potency` to defense.
+    Shield,
+}
+
+impl StatusKind {
+    // Stacking effects add another copy with its own duration. The others
+    // refresh the copy that's already there.
+    pub fn stacks(&self) -> bool {
+        matches!(self, StatusKind::Poison)
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            StatusKind::Poison => "status/poison.png",
+            StatusKind::Stun => "status/stun.png",
+            StatusKind::Slow => "status/slow.png",
+            StatusKind::Shield => "status/shield.png",
+        }
+    }
+}
+
+fn default_potency() -> i32 {
+    1
+}
+
+#[derive(Serialize, Deserialize, Reflect, Clone, Copy, Debug, PartialEq)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    // Turns the effect lasts for after the one it's applied in.
+    pub turns: u32,
+    #[serde(default = "default_potency")]
+    pub potency: i32,
+}
+
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+pub struct StatusEffects(pub Vec<StatusEffect>);
+
+impl StatusEffects {
+    pub fn add(&mut self, effect: StatusEffect) {
+        if !effect.kind.stacks() {
+            if let Some(existing) = self.0.iter_mut().find(|x| x.kind == effect.kind) {
+                existing.turns = existing.turns.max(effect.turns);
+                existing.potency = existing.potency.max(effect.potency);
+                return;
+            }
+        }
+        self.0.push(effect);
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.0.iter().any(|effect| effect.kind == kind)
+    }
+
+    fn total(&self, kind: StatusKind) -> i32 {
+        self.0
+            .iter()
+            .filter(|effect| effect.kind == kind)
+            .map(|effect| effect.potency)
+            .sum()
+    }
+
+    // Runs at the start of every turn, returning the poison damage the unit
+    // takes.
+    pub fn tick(&mut self) -> i32 {
+        // Effects wear off at the start of the turn after their last one, so
+        // an effect lasting one turn still covers the turn after it was applied.
+        self.0.retain(|effect| effect.turns > 0);
+        let poison = self.total(StatusKind::Poison);
+        for effect in self.0.iter_mut() {
+            effect.turns -= 1;
+        }
+        poison
+    }
+
+    pub fn apply(
+        &self,
+        move_range: &MoveRange,
+        attack: &Attack,
+        defense: &Defense,
+    ) -> EffectiveStats {
+        let stunned = self.has(StatusKind::Stun);
+        let move_range = if stunned {
+            0
+        } else {
+            (move_range.0 as i32 - self.total(StatusKind::Slow)).max(0) as u32
+        };
+        EffectiveStats {
+            move_range,
+            attack: attack.0,
+            defense: defense.0 + self.total(StatusKind::Shield),
+            can_act: !stunned,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct StatusIcon;
+
+pub struct StatusEffectsPlugin;
+
+impl Plugin for StatusEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<StatusEffects>().add_systems(
+            Update,
+            (update_effective_stats, update_status_icons).chain(),
+        );
+    }
+}
+
+type StatsChanged = Or<(
+    Changed<StatusEffects>,
+    Changed<MoveRange>,
+    Changed<Attack>,
+    Changed<Defense>,
+)>;
+
+fn update_effective_stats(
+    mut unit_q: Query<
+        (
+            &StatusEffects,
+            &MoveRange,
+            &Attack,
+            &Defense,
+            &mut EffectiveStats,
+        ),
+        StatsChanged,
+    >,
+) {
+    for (status_effects, move_range, attack, defense, mut stats) in unit_q.iter_mut() {
+        stats.set_if_neq(status_effects.apply(move_range, attack, defense));
+    }
+}
+
+fn update_status_icons(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    unit_q: Query<(Entity, &StatusEffects, Option<&Children>), Changed<StatusEffects>>,
+    icon_q: Query<(), With<StatusIcon>>,
+) {
+    for (entity, status_effects, children) in unit_q.iter() {
+        for child in children.into_iter().flatten() {
+            if icon_q.contains(*child) {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+        let mut kinds: Vec<StatusKind> = Vec::new();
+        for effect in status_effects.0.iter() {
+            if !kinds.contains(&effect.kind) {
+                kinds.push(effect.kind);
+            }
+        }
+        // A row of icons centered above the unit's head.
+        let left = -(kinds.len() as f32 - 1.0) * ICON_SIZE / 2.0;
+        commands.entity(entity).with_children(|parent| {
+            for (index, kind) in kinds.iter().enumerate() {
+                parent.spawn((
+                    StatusIcon,
+                    SpriteBundle {
+                        texture: asset_server.load(kind.icon()),
+                        transform: Transform::from_xyz(left + index as f32 * ICON_SIZE, 18.0, 1.0),
+                        ..default()
+                    },
+                ));
+            }
+        });
+    }
+}
@@ -0,0 +1,223 @@
+use bevy::{prelude::*, utils::HashMap};
+use hexx::Hex;
+use serde::Deserialize;
+
+use crate::{
+    components::{BaseHex, Faction, HexTile},
+    constants::STRUCTURE_Z,
+    depth::YSort,
+    events::NewMatch,
+    helpers::{board::Board, data::load_ron_dir},
+    map::MapData,
+    startup::MatchSetup,
+    tiles::layers::{LayerAppExt, LayerAppearance},
+};
+
+// Structures are defined in `assets/structures/*.ron`, and placed on maps by
+// name.
+const STRUCTURES_DIR: &str = "structures";
+
+const NEUTRAL_COLOR: Color = Color::GRAY;
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct StructureDef {
+    pub name: String,
+    // Gold handed to the owner every turn.
+    #[serde(default)]
+    pub income: u32,
+    // Health restored every turn to an owner's unit standing on it.
+    #[serde(default)]
+    pub healing: i32,
+    // Extra range for ranged abilities used from it.
+    #[serde(default)]
+    pub vision: u32,
+    // Its owner can recruit units next to it.
+    #[serde(default)]
+    pub recruits: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct StructureDefs(pub HashMap<String, StructureDef>);
+
+impl StructureDefs {
+    pub fn load() -> Self {
+        StructureDefs(
+            load_ron_dir::<StructureDef>(STRUCTURES_DIR)
+                .into_iter()
+                .map(|def| (def.name.clone(), def))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Structure {
+    pub hex: Hex,
+    pub owner: Option<Faction>,
+    pub def: StructureDef,
+}
+
+// Every structure in the match, and who holds it.
+#[derive(Resource, Default, Clone, Debug)]
+pub struct Structures(pub Vec<Structure>);
+
+impl Structures {
+    pub fn from_map(map: &MapData, defs: &StructureDefs) -> Self {
+        Structures(
+            map.structures
+                .iter()
+                .filter_map(|structure| {
+                    let Some(def) = defs.0.get(&structure.kind) else {
+                        error!("Unknown structure {}", structure.kind);
+                        return None;
+                    };
+                    Some(Structure {
+                        hex: Hex::new(structure.hex.0, structure.hex.1),
+                        owner: structure.owner.map(Faction),
+                        def: def.clone(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    pub fn at(&self, hex: Hex) -> Option<&Structure> {
+        self.0.iter().find(|structure| structure.hex == hex)
+    }
+
+    pub fn vision(&self, hex: Hex) -> u32 {
+        self.at(hex)
+            .map(|structure| structure.def.vision)
+            .unwrap_or_default()
+    }
+
+    // Health restored to a unit of `faction` standing on `hex`.
+    pub fn healing(&self, hex: Hex, faction: Faction) -> i32 {
+        self.at(hex)
+            .filter(|structure| structure.owner == Some(faction))
+            .map(|structure| structure.def.healing)
+            .unwrap_or_default()
+    }
+
+    // Whether `faction` can recruit onto `hex`, from a structure next to it.
+    pub fn recruits_onto(&self, hex: Hex, faction: Faction) -> bool {
+        self.0.iter().any(|structure| {
+            structure.def.recruits
+                && structure.owner == Some(faction)
+                && structure.hex.unsigned_distance_to(hex) == 1
+        })
+    }
+
+    pub fn income(&self, faction: Faction) -> u32 {
+        self.0
+            .iter()
+            .filter(|structure| structure.owner == Some(faction))
+            .map(|structure| structure.def.income)
+            .sum()
+    }
+
+    // Hands the structure on `hex`, if there is one, to `faction`. Returns
+    // whether it changed hands.
+    pub fn capture(&mut self, hex: Hex, faction: Faction) -> bool {
+        match self.0.iter_mut().find(|structure| structure.hex == hex) {
+            Some(structure) if structure.owner != Some(faction) => {
+                structure.owner = Some(faction);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// Marks the tiles structures stand on, for their layer.
+#[derive(Component)]
+pub struct StructureSite;
+
+#[derive(Component)]
+pub struct StructureLabel;
+
+pub struct StructuresPlugin;
+
+impl Plugin for StructuresPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(StructureDefs::load())
+            .init_resource::<Structures>()
+            .register_layer::<StructureSite>(
+                "Structures",
+                0.5,
+                LayerAppearance::Color(Color::rgba(0.55, 0.4, 0.25, 0.7)),
+            )
+            .add_systems(
+                Update,
+                load_structures
+                    .in_set(MatchSetup)
+                    .run_if(on_event::<NewMatch>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    mark_structure_sites,
+                    update_structure_labels.run_if(resource_changed::<Structures>()),
+                )
+                    .chain()
+                    .after(MatchSetup),
+            );
+    }
+}
+
+fn load_structures(
+    map: Res<MapData>,
+    defs: Res<StructureDefs>,
+    mut structures: ResMut<Structures>,
+) {
+    *structures = Structures::from_map(&map, &defs);
+}
+
+fn mark_structure_sites(
+    mut commands: Commands,
+    structures: Res<Structures>,
+    tile_q: Query<(Entity, &HexTile), Added<BaseHex>>,
+) {
+    for (entity, hex_tile) in tile_q.iter() {
+        if structures.at(hex_tile.0).is_some() {
+            commands.entity(entity).insert(StructureSite);
+        }
+    }
+}
+
+// Labels each structure with its initial, in the color of whoever holds it.
+fn update_structure_labels(
+    mut commands: Commands,
+    structures: Res<Structures>,
+    board: Board,
+    label_q: Query<Entity, With<StructureLabel>>,
+) {
+    for entity in label_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for structure in structures.0.iter() {
+        let pos = board.world_pos(structure.hex);
+        let color = structure
+            .owner
+            .map(|faction| faction.color())
+            .unwrap_or(NEUTRAL_COLOR);
+        let initial: String = structure.def.name.chars().take(1).collect();
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    initial,
+                    TextStyle {
+                        font_size: 16.0,
+                        color,
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_xyz(pos.x, pos.y, STRUCTURE_Z),
+                ..default()
+            },
+            YSort::new(STRUCTURE_Z),
+            StructureLabel,
+            Name::new(structure.def.name.clone()),
+        ));
+    }
+}
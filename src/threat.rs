@@ -0,0 +1,173 @@
+use std::collections::HashSet;
+
+use bevy::{ecs::query::Has, prelude::*, utils::HashMap};
+use hexx::Hex;
+
+use crate::{
+    components::{
+        AttackRange, BaseHex, BoardLoc, EffectiveStats, Faction, HexTile, Skirmisher, Unit,
+    },
+    helpers::unit::{movement_range, ZoneOfControl},
+    resources::{Elevation, HexMap, TurnQueue},
+    tiles::layers::{LayerAppExt, LayerAppearance},
+};
+
+// Hexes threatened by one, two, or three or more enemies.
+#[derive(Component)]
+pub struct ThreatLow;
+
+#[derive(Component)]
+pub struct ThreatMedium;
+
+#[derive(Component)]
+pub struct ThreatHigh;
+
+// Enemies that moved or whose stats changed threaten different hexes.
+type ThreatSourceChanged = Or<(Changed<BoardLoc>, Changed<EffectiveStats>)>;
+
+type ThreatLevels = (Has<ThreatLow>, Has<ThreatMedium>, Has<ThreatHigh>);
+type ThreatSource = (
+    &'static BoardLoc,
+    &'static EffectiveStats,
+    &'static AttackRange,
+    &'static Faction,
+    &'static Visibility,
+    Option<&'static Skirmisher>,
+);
+
+#[derive(Resource, Default)]
+pub struct ThreatOverlay {
+    pub visible: bool,
+}
+
+pub struct ThreatPlugin;
+
+impl Plugin for ThreatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ThreatOverlay>()
+            .register_layer::<ThreatLow>(
+                "ThreatLow",
+                2.5,
+                LayerAppearance::Color(Color::rgba(1.0, 0.85, 0.2, 0.35)),
+            )
+            .register_layer::<ThreatMedium>(
+                "ThreatMedium",
+                2.5,
+                LayerAppearance::Color(Color::rgba(1.0, 0.5, 0.1, 0.45)),
+            )
+            .register_layer::<ThreatHigh>(
+                "ThreatHigh",
+                2.5,
+                LayerAppearance::Color(Color::rgba(0.9, 0.1, 0.1, 0.55)),
+            )
+            .add_systems(
+                Update,
+                (
+                    toggle_threat_overlay,
+                    update_threat_overlay.run_if(threats_changed),
+                )
+                    .chain(),
+            );
+    }
+}
+
+// Every hex a unit could attack next turn: anything within attack range of a hex
+// it can move to.
+pub fn threatened_hexes(
+    from: Hex,
+    move_range: u32,
+    attack_range: u32,
+    hex_map: &HexMap,
+    elevation: &Elevation,
+    zone_of_control: &ZoneOfControl,
+) -> HashSet<Hex> {
+    movement_range(from, move_range, hex_map, elevation, zone_of_control)
+        .into_iter()
+        .flat_map(|hex| hex.range(attack_range))
+        .filter(|hex| hex_map.0.contains(hex))
+        .collect()
+}
+
+fn toggle_threat_overlay(keyboard_input: Res<Input<KeyCode>>, mut overlay: ResMut<ThreatOverlay>) {
+    if keyboard_input.just_pressed(KeyCode::T) {
+        overlay.visible = !overlay.visible;
+    }
+}
+
+// The overlay only needs redrawing when it's toggled, or when a unit that
+// threatens hexes moved, died or had its stats changed.
+fn threats_changed(
+    overlay: Res<ThreatOverlay>,
+    moved_q: Query<(), ThreatSourceChanged>,
+    mut removed: RemovedComponents<BoardLoc>,
+    turn_queue: Res<TurnQueue>,
+) -> bool {
+    let any_removed = removed.iter().count() > 0;
+    overlay.is_changed() || turn_queue.is_changed() || !moved_q.is_empty() || any_removed
+}
+
+fn update_threat_overlay(
+    mut commands: Commands,
+    overlay: Res<ThreatOverlay>,
+    enemy_q: Query<ThreatSource, With<Unit>>,
+    tile_q: Query<(Entity, &HexTile, ThreatLevels), With<BaseHex>>,
+    hex_map: Res<HexMap>,
+    elevation: Res<Elevation>,
+    turn_queue: Res<TurnQueue>,
+) {
+    let mut threat_counts: HashMap<Hex, u32> = HashMap::new();
+    if overlay.visible {
+        let positions: Vec<(Faction, Hex)> = enemy_q
+            .iter()
+            .map(|(board_loc, _, _, faction, _, _)| (*faction, board_loc.hex))
+            .collect();
+        for (board_loc, stats, attack_range, faction, visibility, skirmisher) in enemy_q.iter() {
+            // Threats are shown to whoever's turn it is.
+            if *faction == turn_queue.active_faction() || *visibility == Visibility::Hidden {
+                continue;
+            }
+            let zone_of_control =
+                ZoneOfControl::against(*faction, skirmisher.is_some(), positions.iter().copied());
+            for hex in threatened_hexes(
+                board_loc.hex,
+                stats.move_range,
+                attack_range.0,
+                &hex_map,
+                &elevation,
+                &zone_of_control,
+            ) {
+                *threat_counts.entry(hex).or_default() += 1;
+            }
+        }
+    }
+
+    for (entity, hex_tile, (low, medium, high)) in tile_q.iter() {
+        let count = threat_counts.get(&hex_tile.0).copied().unwrap_or_default();
+        let current = match (low, medium, high) {
+            (true, _, _) => 1,
+            (_, true, _) => 2,
+            (_, _, true) => 3,
+            _ => 0,
+        };
+        // Only touch tiles whose level changed, so the layer tiles aren't respawned.
+        if current == count.min(3) {
+            continue;
+        }
+        let mut tile = commands.entity(entity);
+        tile.remove::<ThreatLow>()
+            .remove::<ThreatMedium>()
+            .remove::<ThreatHigh>();
+        match count {
+            0 => (),
+            1 => {
+                tile.insert(ThreatLow);
+            }
+            2 => {
+                tile.insert(ThreatMedium);
+            }
+            _ => {
+                tile.insert(ThreatHigh);
+            }
+        }
+    }
+}
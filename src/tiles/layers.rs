@@ -1,56 +1,167 @@
+use std::marker::PhantomData;
+
 use bevy::prelude::*;
 use hexx::Hex;
 
 use crate::{
-    components::{Activated, BaseHex, HexTile, Hovered, Layer, Selected},
-    constants::LAYOUT,
-    enums::MapLayer,
+    bundles::LayerBundle,
+    components::{Activated, BaseHex, HexTile, Hovered, Layer, LayerId, Selected},
+    constants::OVERLAY_TILE_TEXTURE,
+    depth::YSort,
+    helpers::board::Board,
 };
 
+#[derive(Clone, Debug)]
+pub enum LayerAppearance {
+    Texture(String),
+    // Tints the plain overlay tile, handy for overlays that don't have their own art.
+    Color(Color),
+}
+
+#[derive(Clone, Debug)]
+pub struct LayerDef {
+    pub name: String,
+    pub z: f32,
+    pub appearance: LayerAppearance,
+}
+
+// Layers are added with `register_layer`, which also sets up the systems that
+// draw them, and get their layer entity on the next update.
+#[derive(Resource, Default)]
+pub struct LayerRegistry(pub Vec<LayerDef>);
+
+impl LayerRegistry {
+    fn register(&mut self, name: &str, z: f32, appearance: LayerAppearance) -> LayerId {
+        let id = LayerId(self.0.len());
+        self.0.push(LayerDef {
+            name: String::from(name),
+            z,
+            appearance,
+        });
+        id
+    }
+}
+
+// Remembers which layer a marker component was registered for.
+#[derive(Resource)]
+struct LayerMarker<T: Component> {
+    id: LayerId,
+    _marker: PhantomData<fn() -> T>,
+}
+
+pub trait LayerAppExt {
+    // Registers an overlay layer. Whenever `T` is added to a `BaseHex` tile, a
+    // tile is drawn for that hex in the layer, and removed again with `T`.
+    fn register_layer<T: Component>(
+        &mut self,
+        name: &str,
+        z: f32,
+        appearance: LayerAppearance,
+    ) -> &mut Self;
+}
+
+impl LayerAppExt for App {
+    fn register_layer<T: Component>(
+        &mut self,
+        name: &str,
+        z: f32,
+        appearance: LayerAppearance,
+    ) -> &mut Self {
+        let id = self
+            .world
+            .get_resource_or_insert_with(LayerRegistry::default)
+            .register(name, z, appearance);
+        self.insert_resource(LayerMarker::<T> {
+            id,
+            _marker: PhantomData,
+        })
+        .add_systems(
+            Update,
+            (tile_in_layer_added::<T>, tile_in_layer_removed::<T>),
+        )
+    }
+}
+
 pub struct LayersPlugin;
 
 impl Plugin for LayersPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(
-            Update,
-            (
-                tile_in_layer_added::<Selected, { MapLayer::to_id(MapLayer::SELECTED) }>,
-                tile_in_layer_removed::<Selected, { MapLayer::to_id(MapLayer::SELECTED) }>,
-                tile_in_layer_added::<Hovered, { MapLayer::to_id(MapLayer::HOVERED) }>,
-                tile_in_layer_removed::<Hovered, { MapLayer::to_id(MapLayer::HOVERED) }>,
-                tile_in_layer_added::<Activated, { MapLayer::to_id(MapLayer::ACTIVATED) }>,
-                tile_in_layer_removed::<Activated, { MapLayer::to_id(MapLayer::ACTIVATED) }>,
-            ),
-        );
+        app.init_resource::<LayerRegistry>()
+            .add_systems(Startup, spawn_layers)
+            .add_systems(
+                Update,
+                spawn_layers.run_if(resource_changed::<LayerRegistry>()),
+            )
+            .register_layer::<Activated>(
+                "Activated",
+                1.0,
+                LayerAppearance::Texture(String::from("activated-tile.png")),
+            )
+            .register_layer::<Selected>(
+                "Selected",
+                2.0,
+                LayerAppearance::Texture(String::from("selected-tile.png")),
+            )
+            .register_layer::<Hovered>(
+                "Hovered",
+                3.0,
+                LayerAppearance::Texture(String::from("hovered-tile.png")),
+            );
+    }
+}
+
+// Spawns the layers that don't have an entity yet.
+fn spawn_layers(mut commands: Commands, registry: Res<LayerRegistry>, layer_q: Query<&Layer>) {
+    for (index, layer) in registry.0.iter().enumerate() {
+        if layer_q.iter().any(|spawned| spawned.id == LayerId(index)) {
+            continue;
+        }
+        commands.spawn((
+            Name::new(layer.name.clone()),
+            LayerBundle {
+                layer: Layer { id: LayerId(index) },
+                global_transform: GlobalTransform::from_xyz(0.0, 0.0, 0.0),
+                transform: Transform::from_xyz(0.0, 0.0, 0.0),
+                visibility: Visibility::Visible,
+                computed_visibility: ComputedVisibility::default(),
+            },
+        ));
     }
 }
 
 fn spawn_tiles(
     commands: &mut Commands,
     layer_entity: &Entity,
-    layer: &Layer,
+    layer: &LayerDef,
     hexes: Vec<Hex>,
-    asset: &Handle<Image>,
+    asset_server: &AssetServer,
+    board: &Board,
 ) {
+    let (texture, color): (Handle<Image>, Color) = match &layer.appearance {
+        LayerAppearance::Texture(path) => (asset_server.load(path.as_str()), Color::WHITE),
+        LayerAppearance::Color(color) => (asset_server.load(OVERLAY_TILE_TEXTURE), *color),
+    };
     let children = hexes
         .iter()
         .map(|_| commands.spawn_empty().id())
         .collect::<Vec<_>>();
     commands.entity(*layer_entity).push_children(&children);
 
-    let bundle_batch: Vec<(Entity, (HexTile, SpriteBundle))> = children
+    let bundle_batch: Vec<(Entity, (HexTile, YSort, SpriteBundle))> = children
         .into_iter()
         .zip(hexes.iter().map(|x| {
-            let pos = LAYOUT.hex_to_world_pos(*x);
+            let pos = board.world_pos(*x);
             (
                 HexTile(*x),
+                YSort::new(layer.z),
                 SpriteBundle {
-                    texture: asset.clone(),
-                    transform: Transform::from_xyz(
-                        pos.x,
-                        pos.y,
-                        MapLayer::to_layer_level(&layer.layer_type),
-                    ),
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(board.layout.tile_size),
+                        ..default()
+                    },
+                    texture: texture.clone(),
+                    transform: Transform::from_xyz(pos.x, pos.y, layer.z),
                     ..default()
                 },
             )
@@ -59,47 +170,51 @@ fn spawn_tiles(
     commands.insert_or_spawn_batch(bundle_batch);
 }
 
-fn tile_in_layer_added<T: Component, const LAYER_ID: u32>(
+fn tile_in_layer_added<T: Component>(
     mut commands: Commands,
-    q: Query<&HexTile, (Added<T>, (With<HexTile>, With<BaseHex>))>,
+    q: Query<&HexTile, (Added<T>, With<BaseHex>)>,
     layer_q: Query<(Entity, &Layer)>,
+    marker: Res<LayerMarker<T>>,
+    registry: Res<LayerRegistry>,
     asset_server: Res<AssetServer>,
+    board: Board,
 ) {
     if q.is_empty() {
         return;
     }
-    if let Some(layer_type) = MapLayer::from_id(LAYER_ID) {
-        let texture_handle: Handle<Image> = asset_server.load(MapLayer::get_texture(layer_type));
+    if let Some(layer_def) = registry.0.get(marker.id.0) {
         for (layer_entity, layer) in layer_q.iter() {
-            if layer_type == layer.layer_type {
+            if marker.id == layer.id {
                 spawn_tiles(
                     &mut commands,
                     &layer_entity,
-                    layer,
+                    layer_def,
                     q.iter().map(|x| x.0).collect::<Vec<_>>(),
-                    &texture_handle,
+                    &asset_server,
+                    &board,
                 );
             }
         }
     }
 }
 
-fn tile_in_layer_removed<T: Component, const LAYER_ID: u32>(
+fn tile_in_layer_removed<T: Component>(
     mut commands: Commands,
     mut select_removed: RemovedComponents<T>,
     tile_q: Query<&HexTile, With<BaseHex>>,
     layer_q: Query<(&Layer, &Children)>,
     layer_tiles: Query<&HexTile, Without<BaseHex>>,
+    marker: Res<LayerMarker<T>>,
 ) {
     for hex_tile in tile_q.iter_many(select_removed.iter()) {
-        if let Some(layer_type) = MapLayer::from_id(LAYER_ID) {
-            for (layer, children) in layer_q.iter() {
-                if layer_type == layer.layer_type {
-                    for child in children.iter() {
-                        let hex = layer_tiles.get(*child).unwrap();
-                        if hex.0 == hex_tile.0 {
-                            commands.entity(*child).remove_parent().despawn();
-                        }
+        for (layer, children) in layer_q.iter() {
+            if marker.id == layer.id {
+                for child in children.iter() {
+                    let Ok(hex) = layer_tiles.get(*child) else {
+                        continue;
+                    };
+                    if hex.0 == hex_tile.0 {
+                        commands.entity(*child).remove_parent().despawn();
                     }
                 }
             }
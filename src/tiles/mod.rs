@@ -4,15 +4,19 @@ use bevy::prelude::*;
 use hexx::Hex;
 
 use crate::{
-    components::{Activated, BaseHex, BoardLoc, HexTile, Hovered, Selectable, Selected, Unit},
-    constants::LAYOUT,
+    components::{BaseHex, BoardLoc, HexTile, Hovered, Selectable, Selected, Unit, UnitId},
     events::{
-        ClearLastClicked, HexDoubleClicked, MouseClicked, MouseClickedHex, MouseEnteredHex,
+        ClearLastClicked, CommandSubmitted, MouseClicked, MouseClickedHex, MouseEnteredHex,
         NewTileClicked, UnitDeselected, UnitSelected,
     },
+    game_command::GameCommand,
+    helpers::board::Board,
     resources::{CursorPos, HexMap},
     states::{AppState, PlayerState},
 };
+
+type SelectableUnit = (With<Selectable>, With<Unit>);
+
 #[derive(Default)]
 struct LastHexEntered(pub Option<Hex>);
 
@@ -21,16 +25,14 @@ struct LastTileClicked {
     pub hex: Option<Hex>,
 }
 
-#[derive(Default)]
-struct LastSelectedUnit(pub Option<Entity>);
-
 fn check_mouse_entered_tile(
     cursor_pos: Res<CursorPos>,
     hex_map: Res<HexMap>,
+    board: Board,
     mut ev_writer: EventWriter<MouseEnteredHex>,
     mut local: Local<LastHexEntered>,
 ) {
-    let hex = LAYOUT.world_pos_to_hex(cursor_pos.0);
+    let hex = board.world_pos_to_hex(cursor_pos.0);
     if !hex_map.0.contains(&hex) {
         return;
     };
@@ -85,10 +87,11 @@ fn add_hovered_to_tile(
 fn send_mouse_clicked_hex_event(
     mut ev_mouse_clicked: EventReader<MouseClicked>,
     hex_map: Res<HexMap>,
+    board: Board,
     mut ev_mouse_clicked_tile_writer: EventWriter<MouseClickedHex>,
 ) {
     for ev in ev_mouse_clicked.iter() {
-        let hex = LAYOUT.world_pos_to_hex(ev.0);
+        let hex = board.world_pos_to_hex(ev.0);
         if let Some(x) = hex_map.0.get(&hex) {
             ev_mouse_clicked_tile_writer.send(MouseClickedHex(*x));
         }
@@ -136,36 +139,20 @@ fn send_new_tile_clicked_event(
     }
 }
 
-fn remove_selected_from_unit(
-    mut commands: Commands,
-    mut ev_new_tile_clicked: EventReader<NewTileClicked>,
-    unit_q: Query<(Entity, &BoardLoc), (With<Selectable>, With<Unit>, With<Selected>)>,
-) {
-    if let Some((_, (unit_entity, _))) = ev_new_tile_clicked
-        .iter()
-        .zip(unit_q.iter())
-        .find(|&(ev, (_, board_loc))| board_loc.hex != ev.0)
-    {
-        commands.entity(unit_entity).remove::<Selected>();
-    }
-}
-
 fn add_selected_to_unit(
-    mut commands: Commands,
-    mut ev_new_tile_clicked: EventReader<NewTileClicked>,
-    unit_q: Query<(Entity, &BoardLoc), (With<Selectable>, With<Unit>, Without<Selected>)>,
+    mut ev_mouse_clicked_hex: EventReader<MouseClickedHex>,
+    unit_q: Query<(&UnitId, &BoardLoc), SelectableUnit>,
+    mut ev_command_submitted: EventWriter<CommandSubmitted>,
 ) {
-    if let Some((_, (unit_entity, _))) = ev_new_tile_clicked
-        .iter()
-        .zip(unit_q.iter())
-        .find(|&(ev, (_, board_loc))| board_loc.hex == ev.0)
-    {
-        commands.entity(unit_entity).insert(Selected);
+    for ev in ev_mouse_clicked_hex.iter() {
+        if let Some((unit_id, _)) = unit_q.iter().find(|(_, board_loc)| board_loc.hex == ev.0) {
+            ev_command_submitted.send(CommandSubmitted(GameCommand::SelectUnit(*unit_id)));
+        }
     }
 }
 
 fn send_unit_selected_event(
-    unit_q: Query<Entity, (With<Selectable>, With<Unit>, Added<Selected>)>,
+    unit_q: Query<Entity, (SelectableUnit, Added<Selected>)>,
     mut ev_unit_selected: EventWriter<UnitSelected>,
 ) {
     for unit_entity in unit_q.iter() {
@@ -180,25 +167,7 @@ fn send_unit_deselected_event(
 ) {
     for deselected_entity in unit_selected_removed.iter() {
         if unit_q.contains(deselected_entity) {
-            ev_unit_deselected.send(UnitDeselected(deselected_entity));
-        }
-    }
-}
-
-fn send_hex_double_clicked_event(
-    mut ev_writer: EventWriter<HexDoubleClicked>,
-    mut ev_mouse_clicked_hex: EventReader<MouseClickedHex>,
-    mut last_tile_clicked: Local<LastTileClicked>,
-) {
-    for mouse_clicked_ev in ev_mouse_clicked_hex.iter() {
-        match last_tile_clicked.hex {
-            Some(x) => {
-                if x == mouse_clicked_ev.0 {
-                    ev_writer.send(HexDoubleClicked(x));
-                }
-                last_tile_clicked.hex = Some(mouse_clicked_ev.0);
-            }
-            None => last_tile_clicked.hex = Some(mouse_clicked_ev.0),
+            ev_unit_deselected.send(UnitDeselected);
         }
     }
 }
@@ -217,23 +186,24 @@ impl Plugin for TilePlugin {
                 add_selected_to_tile,
                 add_selected_to_unit,
                 send_new_tile_clicked_event,
-                remove_selected_from_unit,
-                send_unit_selected_event,
-                send_unit_deselected_event,
             )
                 .run_if(in_state(AppState::InGame))
                 .run_if(in_state(PlayerState::Idle)),
         )
+        // The selection can change from any state, e.g. when a click switches
+        // units or the turn passes.
+        .add_systems(
+            Update,
+            (send_unit_selected_event, send_unit_deselected_event)
+                .run_if(in_state(AppState::InGame)),
+        )
         .add_systems(
             Update,
             (
-                send_new_tile_clicked_event,
                 check_mouse_entered_tile,
                 send_mouse_clicked_hex_event,
                 add_hovered_to_tile,
                 remove_hover_from_tile,
-                add_selected_to_tile,
-                send_hex_double_clicked_event,
             )
                 .run_if(in_state(PlayerState::UnitSelected)),
         )
@@ -245,6 +215,16 @@ impl Plugin for TilePlugin {
                 remove_hover_from_tile,
             )
                 .run_if(in_state(PlayerState::UnitMoving)),
+        )
+        .add_systems(
+            Update,
+            (
+                check_mouse_entered_tile,
+                send_mouse_clicked_hex_event,
+                add_hovered_to_tile,
+                remove_hover_from_tile,
+            )
+                .run_if(in_state(PlayerState::Targeting)),
         );
     }
 }
@@ -1,20 +1,66 @@
 use bevy::prelude::*;
 
-use crate::{events::TurnButtonPressed, resources::TurnQueue};
+use crate::{
+    abilities::{can_use_ability, Abilities, AbilityCooldowns, AbilityDefs, ActiveAbility},
+    components::{ActionPoints, EffectiveStats, Selected, Unit},
+    events::{CommandRejected, CommandSubmitted, DefeatSuffered, MatchDrawn, VictoryAchieved},
+    game_command::GameCommand,
+    hot_seat::Players,
+    objectives::Objectives,
+    replay::ReplayPlayback,
+    resources::{Treasury, TurnQueue},
+    states::AppState,
+};
 
 pub struct GameUI;
 
 #[derive(Component)]
 pub struct TurnNumberText;
 
+#[derive(Component)]
+pub struct TurnButton;
+
+#[derive(Component)]
+pub struct ObjectivesText;
+
+#[derive(Component)]
+pub struct MatchResult;
+
+#[derive(Component)]
+pub struct AbilityBarText;
+
+// Says why the last command didn't go through.
+#[derive(Component)]
+pub struct RejectionText;
+
+const REJECTION_DURATION: f32 = 2.5;
+
 impl Plugin for GameUI {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_game_ui)
-            .add_systems(Update, update_turn_number)
-            .add_systems(Update, button_system);
+        app.add_systems(
+            Startup,
+            (
+                spawn_game_ui,
+                spawn_objectives_ui,
+                spawn_ability_bar,
+                spawn_rejection_text,
+            ),
+        )
+        .add_systems(Update, update_turn_number)
+        .add_systems(Update, update_objectives_text)
+        .add_systems(Update, update_ability_bar)
+        .add_systems(Update, show_rejections)
+        .add_systems(Update, show_match_result)
+        .add_systems(OnExit(AppState::GameOver), hide_match_result)
+        .add_systems(
+            Update,
+            button_system.run_if(not(resource_exists::<ReplayPlayback>())),
+        );
     }
 }
 
+type ChangedTurnButton = (Changed<Interaction>, With<TurnButton>);
+
 const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
 const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
@@ -27,11 +73,15 @@ fn button_system(
             &mut BorderColor,
             &Children,
         ),
-        (Changed<Interaction>, With<Button>),
+        ChangedTurnButton,
     >,
     mut text_query: Query<&mut Text>,
-    mut ev_turn_button_pressed: EventWriter<TurnButtonPressed>,
+    turn_queue: Res<TurnQueue>,
+    players: Res<Players>,
+    mut ev_command_submitted: EventWriter<CommandSubmitted>,
 ) {
+    // Only a human player at this machine can end their own turn.
+    let human_turn = players.is_human(turn_queue.active_faction());
     for (interaction, mut color, mut border_color, children) in &mut interaction_query {
         let mut text = text_query.get_mut(children[0]).unwrap();
         match *interaction {
@@ -39,7 +89,9 @@ fn button_system(
                 text.sections[0].value = "Press".to_string();
                 *color = PRESSED_BUTTON.into();
                 border_color.0 = Color::RED;
-                ev_turn_button_pressed.send(TurnButtonPressed);
+                if human_turn {
+                    ev_command_submitted.send(CommandSubmitted(GameCommand::EndTurn));
+                }
             }
             Interaction::Hovered => {
                 text.sections[0].value = "Hover".to_string();
@@ -88,19 +140,22 @@ fn spawn_game_ui(mut commands: Commands) {
             ));
 
             commands
-                .spawn(ButtonBundle {
-                    style: Style {
-                        width: Val::Px(150.0),
-                        height: Val::Px(65.0),
-                        border: UiRect::all(Val::Px(5.0)),
-                        justify_content: JustifyContent::Center,
-                        align_items: AlignItems::Center,
+                .spawn((
+                    TurnButton,
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(150.0),
+                            height: Val::Px(65.0),
+                            border: UiRect::all(Val::Px(5.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::BLACK),
+                        background_color: NORMAL_BUTTON.into(),
                         ..default()
                     },
-                    border_color: BorderColor(Color::BLACK),
-                    background_color: NORMAL_BUTTON.into(),
-                    ..default()
-                })
+                ))
                 .with_children(|parent| {
                     parent.spawn(TextBundle::from_section(
                         "Button",
@@ -117,8 +172,259 @@ fn spawn_game_ui(mut commands: Commands) {
 fn update_turn_number(
     mut texts: Query<&mut Text, With<TurnNumberText>>,
     turn_number: Res<TurnQueue>,
+    players: Res<Players>,
+    treasury: Res<Treasury>,
+) {
+    let faction = turn_number.active_faction();
+    for mut text in &mut texts {
+        text.sections[0].value = format!(
+            "Turn {}: {} ({} gold)",
+            turn_number.turn_number,
+            players.name(faction),
+            treasury.gold(faction)
+        );
+    }
+}
+
+fn spawn_objectives_ui(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "Objectives",
+                TextStyle {
+                    font_size: 20.0,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(12.0),
+                right: Val::Px(10.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+            ..default()
+        },
+        ObjectivesText,
+        Name::new("Objectives"),
+    ));
+}
+
+fn update_objectives_text(
+    mut texts: Query<&mut Text, With<ObjectivesText>>,
+    objectives: Res<Objectives>,
+    turn_number: Res<TurnQueue>,
+    players: Res<Players>,
+) {
+    if !objectives.is_changed() && !turn_number.is_changed() {
+        return;
+    }
+    let lines = objectives
+        .of(players.viewing(turn_number.active_faction()))
+        .iter()
+        .map(|objective| objective.describe(turn_number.turn_number))
+        .collect::<Vec<_>>();
+    for mut text in &mut texts {
+        text.sections[0].value = format!("Objectives\n{}", lines.join("\n"));
+    }
+}
+
+fn spawn_ability_bar(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 18.0,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+            ..default()
+        },
+        AbilityBarText,
+        Name::new("Ability Bar"),
+    ));
+}
+
+fn update_ability_bar(
+    mut texts: Query<&mut Text, With<AbilityBarText>>,
+    selected_q: Query<Entity, (With<Unit>, With<Selected>)>,
+    unit_q: Query<(
+        &Name,
+        &Abilities,
+        &EffectiveStats,
+        &ActionPoints,
+        &AbilityCooldowns,
+    )>,
+    ability_defs: Res<AbilityDefs>,
+    active_ability: Res<ActiveAbility>,
 ) {
+    // The caster loses `Selected` while aiming, so fall back to it.
+    let unit = selected_q
+        .get_single()
+        .ok()
+        .or(active_ability.0.as_ref().map(|targeting| targeting.caster));
+    let value = match unit.and_then(|unit| unit_q.get(unit).ok()) {
+        Some((name, abilities, stats, action_points, cooldowns)) => {
+            let mut lines = vec![format!(
+                "{} - AP {}/{}",
+                name, action_points.current, action_points.max
+            )];
+            for (index, ability) in abilities
+                .0
+                .iter()
+                .enumerate()
+                .filter_map(|(index, name)| Some((index, ability_defs.0.get(name)?)))
+            {
+                let remaining = cooldowns.remaining(&ability.name);
+                let status = if !stats.can_act {
+                    String::from(" (stunned)")
+                } else if remaining > 0 {
+                    format!(" (ready in {})", remaining)
+                } else if !can_use_ability(ability, stats, action_points, cooldowns) {
+                    String::from(" (no AP)")
+                } else {
+                    String::new()
+                };
+                lines.push(format!(
+                    "[{}] {} - {} AP{}",
+                    index + 1,
+                    ability.name,
+                    ability.cost,
+                    status
+                ));
+            }
+            lines.join("\n")
+        }
+        None => String::new(),
+    };
     for mut text in &mut texts {
-        text.sections[0].value = format!("Turn number: {:?}", turn_number.turn_number);
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn spawn_rejection_text(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    bottom: Val::Px(40.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            Name::new("Rejection"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 22.0,
+                        color: Color::rgb(1.0, 0.5, 0.4),
+                        ..default()
+                    },
+                ),
+                RejectionText,
+            ));
+        });
+}
+
+fn show_rejections(
+    time: Res<Time>,
+    mut ev_command_rejected: EventReader<CommandRejected>,
+    mut text_q: Query<&mut Text, With<RejectionText>>,
+    mut shown_for: Local<Option<Timer>>,
+) {
+    if let Some(ev) = ev_command_rejected.iter().last() {
+        for mut text in text_q.iter_mut() {
+            text.sections[0].value = format!("Can't do that: {}", ev.reason);
+        }
+        *shown_for = Some(Timer::from_seconds(REJECTION_DURATION, TimerMode::Once));
+        return;
+    }
+    let Some(timer) = shown_for.as_mut() else {
+        return;
+    };
+    if timer.tick(time.delta()).finished() {
+        *shown_for = None;
+        for mut text in text_q.iter_mut() {
+            text.sections[0].value.clear();
+        }
+    }
+}
+
+// Shown from the side of the human players on this machine. When several of
+// them share it, the winner is named instead.
+fn show_match_result(
+    mut commands: Commands,
+    players: Res<Players>,
+    mut ev_victory: EventReader<VictoryAchieved>,
+    mut ev_defeat: EventReader<DefeatSuffered>,
+    mut ev_drawn: EventReader<MatchDrawn>,
+) {
+    let winner = ev_victory.iter().last().map(|ev| ev.0);
+    let defeated = ev_defeat.iter().count() > 0;
+    let humans = players.humans().count();
+    let drawn = ev_drawn
+        .iter()
+        .last()
+        .is_some_and(|ev| humans == 0 || ev.0.iter().any(|faction| players.is_human(*faction)));
+    let result = match winner {
+        None if drawn => (String::from("Draw"), Color::WHITE),
+        Some(faction) if players.is_human(faction) && humans == 1 => {
+            (String::from("Victory!"), Color::GOLD)
+        }
+        Some(faction) if players.is_human(faction) || humans == 0 => {
+            (format!("{} wins!", players.name(faction)), Color::GOLD)
+        }
+        Some(_) => (String::from("Defeat"), Color::RED),
+        None if defeated => (String::from("Defeat"), Color::RED),
+        None => return,
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..default()
+            },
+            MatchResult,
+            Name::new("Match Result"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                result.0,
+                TextStyle {
+                    font_size: 80.0,
+                    color: result.1,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn hide_match_result(mut commands: Commands, result_q: Query<Entity, With<MatchResult>>) {
+    for entity in result_q.iter() {
+        commands.entity(entity).despawn_recursive();
     }
 }